@@ -32,10 +32,10 @@ fn main() -> anyhow::Result<()> {
     let mut app = App::new();
     add_phase!(app, GamePhase, GamePhase::Bouncing,
       start => [ setup ],
-      run => [ warp_at_edge, collisions, show_performance,
-        continual_parallax, physics_clock, sum_impulses, apply_velocity ],
+      run => [ warp_at_edge, show_performance, continual_parallax ],
       exit => [ cleanup::<BouncyElement> ]
     );
+    app.add_systems(PhysicsUpdate, collisions.in_set(PhysicsSet::Collide));
 
     app.add_plugins(DefaultPlugins.set(WindowPlugin {
         primary_window: Some(Window {
@@ -46,14 +46,16 @@ fn main() -> anyhow::Result<()> {
         ..default()
     }))
     .add_plugins(FrameTimeDiagnosticsPlugin)
-    .add_event::<Impulse>()
-    .add_event::<PhysicsTick>()
     .add_plugins(GameStatePlugin::new(
         GamePhase::MainMenu,
         GamePhase::Bouncing,
         GamePhase::GameOver,
     ))
-    .add_plugins(Random)
+    .add_plugins(
+        PhysicsPlugin::new(GamePhase::Bouncing)
+            .with_config(PhysicsConfig::default().with_gravity(Vec3::ZERO)),
+    )
+    .add_plugins(Random::new())
     .add_plugins(AssetManager::new().add_image("green_ball", "green_ball.png")?)
     .run();
 
@@ -120,6 +122,7 @@ fn warp_at_edge(mut query: Query<&mut Transform, With<Ball>>) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn show_performance(
     mut egui_context: egui::EguiContexts,
     diagnostics: Res<DiagnosticsStore>, //(1)
@@ -147,10 +150,10 @@ fn show_performance(
         ui.colored_label(color, &fps_text);
         ui.colored_label(
             color,
-            &format!("Collision Time: {} ms", collision_time.time),
+            format!("Collision Time: {} ms", collision_time.time),
         );
-        ui.label(&format!("Collision Checks: {}", collision_time.checks));
-        ui.label(&format!("# Balls: {n_balls}"));
+        ui.label(format!("Collision Checks: {}", collision_time.checks));
+        ui.label(format!("# Balls: {n_balls}"));
         if ui.button("Add Ball").clicked() {
             //(6)
             println!(
@@ -183,11 +186,7 @@ fn bounce_on_collision(
     impulse: &mut EventWriter<Impulse>,
 ) {
     let a_to_b = (ball_a - ball_b).normalize(); //(7)
-    impulse.send(Impulse {
-        target: entity,
-        amount: a_to_b / 8.0, //(8)
-        absolute: false,
-    });
+    impulse.send(Impulse::new(entity, a_to_b / 8.0)); //(8)
 }
 
 fn collisions(
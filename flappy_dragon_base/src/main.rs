@@ -10,6 +10,10 @@ struct Obstacle;
 #[derive(Component)]
 struct FlappyElement;
 
+asset_tags! {
+    enum FlappyAsset from "manifest.ron"
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
 enum GamePhase {
     #[default]
@@ -20,15 +24,41 @@ enum GamePhase {
 }
 
 fn main() -> anyhow::Result<()> {
+    let game_states = GameStatePlugin::new(
+        GamePhase::MainMenu,
+        GamePhase::Flapping,
+        GamePhase::GameOver,
+    );
+    let assets = AssetManager::new();
+    // Build the assets into the executable, so it runs from any directory.
+    #[cfg(feature = "embed_assets")]
+    let assets = assets.with_embedded(embedded_assets![
+        "main_menu.png",
+        "game_over.png",
+        "manifest.ron",
+        "flappy_dragon.png",
+        "wall.png",
+        "dragonflap.ogg",
+        "crash.ogg",
+        "flappy_sprite_sheet.png",
+        "rocky-far-mountains.png",
+        "rocky-nowater-far.png",
+        "rocky-nowater-mid.png",
+        "rocky-nowater-close.png",
+    ]);
+    let assets = assets
+        .add_manifest("manifest.ron")?
+        .check_tags(&game_states.required_assets())?;
+
     let mut app = App::new();
 
     add_phase!(app, GamePhase, GamePhase::Flapping,
       start => [ setup ],
-      run => [ flap, clamp, move_walls, hit_wall, cycle_animations,
-        continual_parallax, physics_clock, sum_impulses, apply_gravity,
-        apply_velocity, check_collisions::<Flappy, Obstacle>, rotate],
+      run => [ clamp, move_walls, hit_wall, cycle_animations,
+        continual_parallax],
       exit => [ cleanup::<FlappyElement> ]
     );
+    app.add_systems(PhysicsUpdate, (flap, level_out).in_set(PhysicsSet::Input));
 
     app.add_plugins(DefaultPlugins.set(WindowPlugin {
         primary_window: Some(Window {
@@ -39,24 +69,25 @@ fn main() -> anyhow::Result<()> {
         }),
         ..default()
     }))
-    .add_plugins(Random)
-    .add_plugins(GameStatePlugin::new(
-        GamePhase::MainMenu,
-        GamePhase::Flapping,
-        GamePhase::GameOver,
-    ))
     .add_plugins(
-        AssetManager::new()
-            .add_image("dragon", "flappy_dragon.png")?
-            .add_image("wall", "wall.png")?
-            .add_sound("flap", "dragonflap.ogg")?
-            .add_sound("crash", "crash.ogg")?
-            .add_sprite_sheet("flappy", "flappy_sprite_sheet.png", 62.0, 65.0, 4, 1)?
-            .add_image("bg_static", "rocky-far-mountains.png")?
-            .add_image("bg_far", "rocky-nowater-far.png")?
-            .add_image("bg_mid", "rocky-nowater-mid.png")?
-            .add_image("bg_close", "rocky-nowater-close.png")?,
+        Random::new()
+            .with_stream("level")
+            .with_seed_from_env("FLAPPY_SEED")
+            .with_seed_from_args("--seed"),
+    )
+    .add_plugins(game_states)
+    .add_plugins(
+        PhysicsPlugin::new(GamePhase::Flapping)
+            .with_config(
+                PhysicsConfig::default()
+                    .with_gravity(Vec3::new(0.0, -0.75, 0.0))
+                    .with_max_speed(12.0)
+                    .with_angular_damping(0.4),
+            )
+            .with_collisions::<Flappy, Obstacle>(),
     )
+    .add_plugins(assets)
+    .insert_resource(AudioMixer::default().with_max_instances(FlappyAsset::Flap, 2))
     .insert_resource(
         Animations::new()
             .with_animation(
@@ -74,7 +105,7 @@ fn main() -> anyhow::Result<()> {
                         66,
                         vec![
                             AnimationOption::NextFrame,
-                            AnimationOption::PlaySound("flap".to_string()),
+                            AnimationOption::PlaySound(FlappyAsset::Flap.to_string()),
                         ],
                     ),
                     AnimationFrame::new(1, 66, vec![AnimationOption::NextFrame]),
@@ -91,25 +122,24 @@ fn main() -> anyhow::Result<()> {
                 ]),
             ),
     )
-    .add_event::<OnCollision<Flappy, Obstacle>>()
     .run();
     Ok(())
 }
 
 fn setup(
     mut commands: Commands,
-    mut rng: ResMut<RandomNumberGenerator>,
+    mut rng: ResMut<RandomStreams>,
     assets: Res<AssetStore>,
     loaded_assets: AssetResource,
 ) {
     commands
         .spawn(Camera2dBundle::default())
         .insert(FlappyElement);
-    build_wall(&mut commands, &assets, &loaded_assets, rng.range(-5..5));
+    build_wall(&mut commands, &assets, rng.stream("level").range(-5..5));
     spawn_animated_sprite!(
         assets,
         commands,
-        "flappy",
+        FlappyAsset::Flappy,
         -490.0,
         0.0,
         10.0,
@@ -118,6 +148,9 @@ fn setup(
         FlappyElement,
         Velocity::default(),
         ApplyGravity,
+        AngularVelocity::default(),
+        RotationalInertia(1000.0),
+        Torque::default(),
         AxisAlignedBoundingBox::new(62.0, 65.0),
         PhysicsPosition::new(Vec2::new(-490.0, 0.0))
     );
@@ -125,7 +158,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_static",
+        FlappyAsset::BgStatic,
         0.0,
         0.0,
         1.0,
@@ -135,7 +168,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_far",
+        FlappyAsset::BgFar,
         0.0,
         0.0,
         2.0,
@@ -146,7 +179,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_far",
+        FlappyAsset::BgFar,
         1280.0,
         0.0,
         2.0,
@@ -157,7 +190,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_mid",
+        FlappyAsset::BgMid,
         0.0,
         0.0,
         3.0,
@@ -168,7 +201,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_mid",
+        FlappyAsset::BgMid,
         1280.0,
         0.0,
         3.0,
@@ -179,7 +212,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_close",
+        FlappyAsset::BgClose,
         0.0,
         0.0,
         4.0,
@@ -190,7 +223,7 @@ fn setup(
     spawn_image!(
         assets,
         commands,
-        "bg_close",
+        FlappyAsset::BgClose,
         1280.0,
         0.0,
         4.0,
@@ -200,22 +233,16 @@ fn setup(
     );
 }
 
-fn build_wall(
-    commands: &mut Commands,
-    assets: &AssetStore,
-    loaded_assets: &LoadedAssets,
-    gap_y: i32,
-) {
+fn build_wall(commands: &mut Commands, assets: &AssetStore, gap_y: i32) {
     for y in -12..=12 {
         if y < gap_y - 4 || y > gap_y + 4 {
-            spawn_image!(
+            spawn_packed_image!(
                 assets,
                 commands,
-                "wall",
+                FlappyAsset::Wall,
                 512.0,
                 y as f32 * 32.0,
                 10.0,
-                &loaded_assets,
                 Obstacle,
                 FlappyElement,
                 Velocity::new(-10.0, 0.0, 0.0),
@@ -233,11 +260,8 @@ fn flap(
 ) {
     if keyboard.pressed(KeyCode::Space) {
         if let Ok((flappy, mut animation)) = query.get_single_mut() {
-            impulse.send(Impulse {
-                target: flappy,
-                amount: Vec3::Y,
-                absolute: false,
-            });
+            // Flapping pushes from in front of the dragon's centre, lifting its nose.
+            impulse.send(Impulse::new(flappy, Vec3::Y * 2.0).at(Vec2::new(10.0, 0.0)));
             animation.switch("Flapping");
         }
     }
@@ -258,8 +282,7 @@ fn move_walls(
     query: Query<&Transform, With<Obstacle>>,
     delete: Query<Entity, With<Obstacle>>,
     assets: Res<AssetStore>,
-    mut rng: ResMut<RandomNumberGenerator>,
-    loaded_assets: AssetResource,
+    mut rng: ResMut<RandomStreams>,
 ) {
     let mut rebuild = false;
     for transform in query.iter() {
@@ -272,7 +295,7 @@ fn move_walls(
         for entity in delete.iter() {
             commands.entity(entity).despawn();
         }
-        build_wall(&mut commands, &assets, &loaded_assets, rng.range(-5..5));
+        build_wall(&mut commands, &assets, rng.stream("level").range(-5..5));
     }
 }
 
@@ -281,21 +304,23 @@ fn hit_wall(
     mut state: ResMut<NextState<GamePhase>>,
     assets: Res<AssetStore>,
     loaded_assets: Res<LoadedAssets>,
+    positions: Query<&PhysicsPosition>,
     mut commands: Commands,
 ) {
-    for _collision in collisions.read() {
-        assets.play("crash", &mut commands, &loaded_assets);
-        let _ = state.set(GamePhase::GameOver);
+    for collision in collisions.read() {
+        let position = positions
+            .get(collision.entity_a)
+            .map_or(Vec2::ZERO, |position| position.end_frame);
+        assets.play_at(FlappyAsset::Crash, position, &mut commands, &loaded_assets);
+        state.set(GamePhase::GameOver);
     }
 }
 
-fn rotate(mut physics_position: Query<(&PhysicsPosition, &mut Transform), With<Flappy>>) {
-    physics_position.for_each_mut(|(position, mut transform)| {
-        if position.start_frame != position.end_frame {
-            let start = position.start_frame;
-            let end = position.end_frame;
-            let angle = end.angle_between(start) * 10.0;
-            transform.rotation = Quat::from_rotation_z(angle);
-        }
-    });
+fn level_out(mut query: Query<(&Velocity, &PhysicsPosition, &mut Torque), With<Flappy>>) {
+    // The air rushing past turns the dragon to face the way it is flying.
+    const AIRSPEED: f32 = 20.0;
+    for (velocity, position, mut torque) in query.iter_mut() {
+        let heading = velocity.get().y.atan2(AIRSPEED);
+        torque.0 = (heading - position.end_rotation) * 200.0;
+    }
 }
@@ -1,14 +1,101 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use my_library_docs::*;
+//! Benchmarks for `RandomNumberGenerator`.
+//!
+//! Each run measures the backend selected by feature flags, and names its
+//! results after it so that criterion keeps them side by side. To compare
+//! every backend, and the locking path of each, run:
+//!
+//! ```text
+//! cargo bench --bench random --no-default-features --features pcg,locking
+//! cargo bench --bench random --no-default-features --features xorshift,locking
+//! cargo bench --bench random --no-default-features --features locking
+//! ```
+//!
+//! Dropping `locking` skips the mutex benchmarks.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use my_library::*;
 
-pub fn criterion_benchmark(c: &mut Criterion) {
-  c.bench_function("random", |b| {
-    let mut rng = RandomNumberGenerator::new();
-    b.iter(|| {
-      rng.range(1.0_f32..10_000_000_f32);
-    })
+const THREADS: usize = 4;
+const DRAWS_PER_THREAD: usize = 10_000;
+
+pub fn unlocked_benchmark(c: &mut Criterion) {
+  let mut group = c.benchmark_group(RNG_ALGORITHM);
+  let mut rng = UnlockedRandomNumberGenerator::seeded(1);
+  group.bench_function("random_u32", |b| b.iter(|| rng.random::<u32>()));
+  group.bench_function("random_u64", |b| b.iter(|| rng.random::<u64>()));
+  group.bench_function("random_f32", |b| b.iter(|| rng.random::<f32>()));
+  group.bench_function("range_i32", |b| b.iter(|| rng.range(1..10_000)));
+  group.bench_function("range_f32", |b| {
+    b.iter(|| rng.range(1.0_f32..10_000_000_f32))
+  });
+  group.finish();
+}
+
+#[cfg(feature = "locking")]
+pub fn locking_benchmark(c: &mut Criterion) {
+  let mut group = c.benchmark_group(format!("{RNG_ALGORITHM}/locking"));
+  let rng = RandomNumberGenerator::seeded(1);
+  group.bench_function("random_u32", |b| b.iter(|| rng.random::<u32>()));
+  group.bench_function("random_u64", |b| b.iter(|| rng.random::<u64>()));
+  group.bench_function("random_f32", |b| b.iter(|| rng.random::<f32>()));
+  group.bench_function("range_i32", |b| b.iter(|| rng.range(1..10_000)));
+  group.bench_function("range_f32", |b| {
+    b.iter(|| rng.range(1.0_f32..10_000_000_f32))
+  });
+  group.finish();
+}
+
+/// Every thread draws from one generator behind a lock, which is what
+/// the `locking` feature does internally.
+fn shared_generator(rng: &std::sync::Mutex<UnlockedRandomNumberGenerator>) {
+  std::thread::scope(|scope| {
+    for _ in 0..THREADS {
+      scope.spawn(|| {
+        for _ in 0..DRAWS_PER_THREAD {
+          rng.lock().unwrap().range(1..10_000);
+        }
+      });
+    }
+  });
+}
+
+/// Every thread draws from its own unlocked generator, split from the
+/// master.
+fn split_generators(children: Vec<UnlockedRandomNumberGenerator>) {
+  std::thread::scope(|scope| {
+    for mut rng in children {
+      scope.spawn(move || {
+        for _ in 0..DRAWS_PER_THREAD {
+          rng.range(1..10_000);
+        }
+      });
+    }
+  });
+}
+
+pub fn parallel_benchmark(c: &mut Criterion) {
+  let mut group = c.benchmark_group(format!("{RNG_ALGORITHM}/parallel"));
+  group.bench_function("shared", |b| {
+    let rng = std::sync::Mutex::new(UnlockedRandomNumberGenerator::seeded(1));
+    b.iter(|| shared_generator(&rng))
+  });
+  group.bench_function("split", |b| {
+    let mut master = UnlockedRandomNumberGenerator::seeded(1);
+    b.iter_batched(
+      || (0..THREADS).map(|_| master.split()).collect(),
+      split_generators,
+      BatchSize::SmallInput,
+    )
   });
+  group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+#[cfg(not(feature = "locking"))]
+criterion_group!(benches, unlocked_benchmark, parallel_benchmark);
+#[cfg(feature = "locking")]
+criterion_group!(
+  benches,
+  unlocked_benchmark,
+  locking_benchmark,
+  parallel_benchmark
+);
 criterion_main!(benches);
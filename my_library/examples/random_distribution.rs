@@ -1,15 +1,15 @@
 //! Roll 3d6 repeatedly and graph the resulting distribution.
-use my_library_docs::RandomNumberGenerator;
+use my_library::RandomNumberGenerator;
 
 fn main() {
   // Create a random number generator
+  #[cfg_attr(feature = "locking", allow(unused_mut))]
   let mut rng = RandomNumberGenerator::new();
   // Store the results (minus 3)
-  let mut results = vec![0; 16];
+  let mut results = [0; 16];
   // Roll 1,000 sets of 3d6 and increment results to map distribution
   for _ in 0..1_000 {
-    let roll: usize =
-      rng.range(1..=6) + rng.range(1..=6) + rng.range(1..=6);
+    let roll = rng.roll_dice("3d6").unwrap() as usize;
     results[roll - 3] += 1;
   }
   // Print the distribution histogram
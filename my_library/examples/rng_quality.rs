@@ -0,0 +1,78 @@
+//! Check the statistical quality of the selected random number generator
+//! with chi-square tests over evenly sized buckets.
+//!
+//! Run it against each backend with, for example:
+//! `cargo run --release --example rng_quality --no-default-features --features xorshift`
+use my_library::{UnlockedRandomNumberGenerator, RNG_ALGORITHM};
+
+const SAMPLES: usize = 1_000_000;
+const BUCKETS: usize = 100;
+
+/// Compares observed bucket counts against a uniform distribution.
+fn chi_square(counts: &[usize]) -> f64 {
+  let expected = counts.iter().sum::<usize>() as f64 / counts.len() as f64;
+  counts
+    .iter()
+    .map(|observed| (*observed as f64 - expected).powi(2) / expected)
+    .sum()
+}
+
+/// The chi-square value a fair generator stays below 99% of the time,
+/// using the Wilson-Hilferty approximation.
+fn critical_value(degrees_of_freedom: usize) -> f64 {
+  const Z_99: f64 = 2.326;
+  let k = degrees_of_freedom as f64;
+  let term = 2.0 / (9.0 * k);
+  k * (1.0 - term + Z_99 * term.sqrt()).powi(3)
+}
+
+fn report(name: &str, counts: &[usize]) -> bool {
+  let statistic = chi_square(counts);
+  let critical = critical_value(counts.len() - 1);
+  let pass = statistic < critical;
+  println!(
+    "{name: <24} chi-square {statistic: >8.2} (critical {critical:.2}) {}",
+    if pass { "PASS" } else { "FAIL" }
+  );
+  pass
+}
+
+fn main() {
+  let mut rng = UnlockedRandomNumberGenerator::seeded(1);
+  println!("Testing the {RNG_ALGORITHM} backend with {SAMPLES} samples per test");
+  let mut passed = true;
+
+  let mut counts = [0; BUCKETS];
+  (0..SAMPLES).for_each(|_| counts[rng.range(0..BUCKETS)] += 1);
+  passed &= report("range (usize)", &counts);
+
+  let mut counts = [0; BUCKETS];
+  (0..SAMPLES).for_each(|_| {
+    counts[(rng.range(0.0_f32..1.0) * BUCKETS as f32) as usize] += 1
+  });
+  passed &= report("range (f32)", &counts);
+
+  let mut counts = [0; BUCKETS];
+  (0..SAMPLES).for_each(|_| counts[(rng.random::<u64>() % BUCKETS as u64) as usize] += 1);
+  passed &= report("random (u64, low bits)", &counts);
+
+  let mut counts = [0; 256];
+  (0..SAMPLES).for_each(|_| counts[(rng.random::<u64>() >> 56) as usize] += 1);
+  passed &= report("random (u64, high byte)", &counts);
+
+  let mut counts = [0; 16];
+  (0..SAMPLES).for_each(|_| counts[(rng.roll_dice("1d16").unwrap() - 1) as usize] += 1);
+  passed &= report("roll_dice (1d16)", &counts);
+
+  // Pairs of consecutive draws should be independent, too.
+  let mut counts = [0; 100];
+  (0..SAMPLES).for_each(|_| {
+    let pair = rng.range(0..10) * 10 + rng.range(0..10);
+    counts[pair] += 1;
+  });
+  passed &= report("serial pairs", &counts);
+
+  if !passed {
+    std::process::exit(1);
+  }
+}
@@ -1,9 +1,30 @@
 mod asset_manager;
 pub use asset_manager::AssetManager;
+pub(crate) use asset_manager::{AssetEntry, AssetType};
+
+mod asset_tag;
+pub use asset_tag::*;
+#[doc(hidden)]
+pub use my_library_macros::asset_tags_from_manifest;
 
 mod asset_store;
 pub use asset_store::*;
 
+mod audio;
+pub use audio::*;
+
+mod data_loader;
+pub(crate) use data_loader::DataRegistration;
+
+mod sprite_sheet;
+pub(crate) use sprite_sheet::*;
+
+mod hot_reload;
+pub(crate) use hot_reload::*;
+
+mod load_failure;
+pub use load_failure::*;
+
 mod loading_menu;
 pub(crate) use loading_menu::*;
 
@@ -11,7 +32,12 @@ pub(crate) use loading_menu::*;
 macro_rules! spawn_image {
     ($assets:expr, $commands:expr, $index:expr, $x:expr, $y:expr, $z:expr, $resource:expr, $($component:expr),*) => {
       $commands.spawn(SpriteBundle {
-        texture: $assets.get_handle($index, $resource).unwrap(),
+        texture: $assets
+          .try_get_handle($index, $resource)
+          .unwrap_or_else(|error| {
+            bevy::log::error!("{error}");
+            Default::default()
+          }),
         transform: Transform::from_xyz($x, $y, $z),
         ..default()
       })
@@ -20,3 +46,43 @@ macro_rules! spawn_image {
       )*
     };
 }
+
+/// Spawns an image packed with
+/// [`AssetManager::pack_images`](crate::AssetManager::pack_images), drawn
+/// from its shared atlas.
+#[macro_export]
+macro_rules! spawn_packed_image {
+    ($assets:expr, $commands:expr, $index:expr, $x:expr, $y:expr, $z:expr, $($component:expr),*) => {{
+      let (texture_atlas, index) = $assets
+        .try_get_packed_image($index)
+        .unwrap_or_else(|error| {
+          bevy::log::error!("{error}");
+          Default::default()
+        });
+      $commands.spawn(SpriteSheetBundle {
+        texture_atlas,
+        sprite: TextureAtlasSprite::new(index),
+        transform: Transform::from_xyz($x, $y, $z),
+        ..default()
+      })
+      $(
+      .insert($component)
+      )*
+    }};
+}
+
+/// Builds files from the `assets` directory beside the calling crate's
+/// `Cargo.toml` into the executable, for
+/// [`AssetManager::with_embedded`](crate::AssetManager::with_embedded).
+/// Filenames are relative to the `assets` directory.
+#[macro_export]
+macro_rules! embedded_assets {
+    ($($filename:literal),* $(,)?) => {
+      &[$(
+        (
+          $filename,
+          include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $filename)) as &'static [u8],
+        )
+      ),*]
+    };
+}
@@ -1,10 +1,18 @@
-use crate::{AssetStore, FutureAtlas};
-use bevy::app::{Plugin, Startup};
-use bevy::asset::AssetServer;
+use crate::{
+    AssetStore, AssetTag, DataRegistration, ImagePack, PackedSheet, GAME_OVER_ASSET,
+    MAIN_MENU_ASSET,
+};
+use anyhow::Context;
+use bevy::app::{Plugin, Startup, Update};
+use bevy::asset::{io::embedded::EmbeddedAssetRegistry, Asset, AssetPlugin, AssetServer};
 use bevy::ecs::system::{Commands, Res, Resource};
+use bevy::log::warn;
 use bevy::math::Vec2;
+use bevy::utils::HashMap;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::path::Path;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
 pub enum AssetType {
     Image,
     Sound,
@@ -12,50 +20,318 @@ pub enum AssetType {
         tile_size: Vec2,
         sprites_x: usize,
         sprites_y: usize,
+        /// Space between sprites.
+        #[serde(default)]
+        padding: Option<Vec2>,
+        /// Space before the first sprite.
+        #[serde(default)]
+        offset: Option<Vec2>,
     },
+    /// A sprite sheet exported as JSON by TexturePacker or Aseprite, whose
+    /// sprites can be any size. Register the JSON file; the image it names
+    /// is loaded in its place.
+    PackedSpriteSheet,
+    /// A packed sprite sheet once its JSON has been read.
+    #[serde(skip)]
+    PackedLayout(PackedSheet),
+    Font,
+    Shader,
+    /// Audio meant to loop in the background, such as level music.
+    Music,
+    /// A RON or JSON file deserialized into a game type. Data has to be
+    /// added with [`AssetManager::add_data`], which knows the type.
+    #[serde(skip)]
+    Data,
+}
+
+/// An asset registered with the [`AssetManager`].
+#[derive(Clone)]
+pub(crate) struct AssetEntry {
+    pub(crate) tag: String,
+    pub(crate) filename: String,
+    pub(crate) asset_type: AssetType,
+    /// The group the asset is loaded with, or `None` to load it at startup.
+    pub(crate) group: Option<String>,
+}
+
+impl AssetEntry {
+    fn new(tag: String, filename: String, asset_type: AssetType) -> Self {
+        Self {
+            tag,
+            filename,
+            asset_type,
+            group: None,
+        }
+    }
+
+    /// The tag the file is stored under in the [`AssetStore`]. Sprite sheets
+    /// store their image under a separate tag from the atlas.
+    pub(crate) fn index_tag(&self) -> String {
+        match self.asset_type {
+            AssetType::SpriteSheet { .. } | AssetType::PackedLayout(_) => {
+                format!("{}_base", self.tag)
+            }
+            _ => self.tag.clone(),
+        }
+    }
 }
 
 #[derive(Resource, Clone)]
 pub struct AssetManager {
-    asset_list: Vec<(String, String, AssetType)>,
+    asset_list: Vec<AssetEntry>,
+    data_loaders: Vec<DataRegistration>,
+    fallbacks: HashMap<String, String>,
+    packs: Vec<ImagePack>,
+    /// The directory files are read from, relative to Bevy's base path.
+    asset_folder: String,
+    /// Files built into the executable, by filename.
+    embedded: HashMap<String, &'static [u8]>,
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AssetManager {
+    /// Creates an `AssetManager` that registers `main_menu.png` and
+    /// `game_over.png` as the menu images used by
+    /// [`GameStatePlugin`](crate::GameStatePlugin). Use
+    /// [`AssetManager::empty`] if your game doesn't have them.
     pub fn new() -> Self {
         Self {
             asset_list: vec![
-                (
-                    "main_menu".to_string(),
+                AssetEntry::new(
+                    MAIN_MENU_ASSET.to_string(),
                     "main_menu.png".to_string(),
                     AssetType::Image,
                 ),
-                (
-                    "game_over".to_string(),
+                AssetEntry::new(
+                    GAME_OVER_ASSET.to_string(),
                     "game_over.png".to_string(),
                     AssetType::Image,
                 ),
             ],
+            data_loaders: Vec::new(),
+            fallbacks: HashMap::new(),
+            packs: Vec::new(),
+            asset_folder: "assets".to_string(),
+            embedded: HashMap::new(),
+        }
+    }
+
+    /// Creates an `AssetManager` with no assets registered.
+    pub fn empty() -> Self {
+        Self {
+            asset_list: Vec::new(),
+            data_loaders: Vec::new(),
+            fallbacks: HashMap::new(),
+            packs: Vec::new(),
+            asset_folder: "assets".to_string(),
+            embedded: HashMap::new(),
         }
     }
 
-    pub fn add_image<S: ToString>(mut self, tag: S, filename: S) -> anyhow::Result<Self> {
+    /// Creates an `AssetManager` from a manifest file in the `assets`
+    /// directory, so assets can be added without recompiling. See
+    /// [`AssetManager::add_manifest`] for the format.
+    pub fn from_manifest<S: ToString>(filename: S) -> anyhow::Result<Self> {
+        Self::new().add_manifest(filename)
+    }
+
+    /// Sets the directory asset files are read from, to match a custom
+    /// `AssetPlugin::file_path`. Call it before adding any assets, since
+    /// each file is checked as it is added.
+    ///
+    /// Like Bevy, the directory is relative to `BEVY_ASSET_ROOT` if it is
+    /// set, or else `CARGO_MANIFEST_DIR`, which `cargo run` sets, or else
+    /// the executable's directory. So it doesn't matter which directory the
+    /// game is started from.
+    pub fn with_asset_path<S: ToString>(mut self, path: S) -> Self {
+        self.asset_folder = path.to_string();
+        self
+    }
+
+    /// Builds files into the executable, so that a packaged game runs
+    /// without its `assets` directory. Embedded files are used in place of
+    /// files on disk with the same name---including manifests, which should
+    /// be embedded before they are added. Make the list with
+    /// [`embedded_assets!`](crate::embedded_assets):
+    ///
+    /// ```ignore
+    /// # use my_library::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let assets = AssetManager::new()
+    ///     .with_embedded(embedded_assets!["main_menu.png", "game_over.png", "manifest.ron", "wall.png"])
+    ///     .add_manifest("manifest.ron")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_embedded(mut self, files: &[(&'static str, &'static [u8])]) -> Self {
+        for (filename, bytes) in files.iter() {
+            self.embedded.insert(filename.to_string(), bytes);
+        }
+        self
+    }
+
+    /// Registers every asset listed in a RON manifest file in the `assets`
+    /// directory. Each entry names a tag, a filename and an [`AssetType`]:
+    ///
+    /// ```ron
+    /// (
+    ///     assets: [
+    ///         (tag: "wall", filename: "wall.png", asset_type: Image),
+    ///         (tag: "flap", filename: "dragonflap.ogg", asset_type: Sound),
+    ///         (tag: "theme", filename: "theme.ogg", asset_type: Music),
+    ///         (tag: "score", filename: "score_font.ttf", asset_type: Font),
+    ///         (tag: "hero", filename: "hero.json", asset_type: PackedSpriteSheet),
+    ///         (
+    ///             tag: "flappy",
+    ///             filename: "flappy_sprite_sheet.png",
+    ///             asset_type: SpriteSheet(tile_size: (62.0, 65.0), sprites_x: 4, sprites_y: 1),
+    ///         ),
+    ///     ],
+    /// )
+    /// ```
+    ///
+    /// Add `group: Some("level2")` to an entry to load it with a group
+    /// rather than at startup; see [`AssetManager::with_group`]. Images can
+    /// be packed into shared atlases, as with [`AssetManager::pack_images`],
+    /// by adding `packs: [(tag: "sprites", images: ["wall", "dragon"])]`.
+    ///
+    /// Data files need a Rust type, so they can't be listed in a manifest;
+    /// register them with [`AssetManager::add_data`].
+    pub fn add_manifest<S: ToString>(mut self, filename: S) -> anyhow::Result<Self> {
         let filename = filename.to_string();
-        AssetManager::asset_exists(&filename)?;
-        self.asset_list
-            .push((tag.to_string(), filename, AssetType::Image));
+        self.asset_exists(&filename)?;
+        let manifest = self
+            .read_asset(&filename)
+            .with_context(|| format!("unable to read manifest {filename}"))?;
+        let manifest = AssetManifest::parse(&manifest)
+            .with_context(|| format!("unable to parse manifest {filename}"))?;
+        for entry in manifest.assets {
+            self = self
+                .add_asset(entry.tag, entry.filename, entry.asset_type)
+                .with_context(|| format!("in manifest {filename}"))?;
+            if let Some(asset) = self.asset_list.last_mut() {
+                asset.group = entry.group;
+            }
+        }
+        for pack in manifest.packs {
+            self = self
+                .pack_images(pack.tag, pack.images)
+                .with_context(|| format!("in manifest {filename}"))?;
+        }
         Ok(self)
     }
 
-    pub fn add_sound<S: ToString>(mut self, tag: S, filename: S) -> anyhow::Result<Self> {
-        let filename = filename.to_string();
-        AssetManager::asset_exists(&filename)?;
-        self.asset_list
-            .push((tag.to_string(), filename, AssetType::Sound));
+    pub fn add_image<S: ToString>(self, tag: S, filename: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, filename, AssetType::Image)
+    }
+
+    pub fn add_sound<S: ToString>(self, tag: S, filename: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, filename, AssetType::Sound)
+    }
+
+    /// Adds a font (`.ttf` or `.otf`), for use in `Text` sections.
+    pub fn add_font<S: ToString>(self, tag: S, filename: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, filename, AssetType::Font)
+    }
+
+    /// Adds a WGSL or GLSL shader.
+    pub fn add_shader<S: ToString>(self, tag: S, filename: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, filename, AssetType::Shader)
+    }
+
+    /// Adds a music track. Music loads like a sound, but
+    /// [`AssetStore::play_music`] loops it and crossfades between tracks.
+    pub fn add_music<S: ToString>(self, tag: S, filename: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, filename, AssetType::Music)
+    }
+
+    /// Adds a data file, such as a level definition, that is deserialized
+    /// into `T` when it loads. Files whose extension ends in `json` are read
+    /// as JSON; anything else is read as RON.
+    ///
+    /// Bevy chooses a loader by extension, so data files need a compound
+    /// extension that names their type---`first.level.ron` rather than
+    /// `first.ron`---and each data type needs its own. Registering a plain
+    /// `ron` or `json` extension, or one another type already uses, is an
+    /// error.
+    ///
+    /// ```no_run
+    /// # use my_library::*;
+    /// # use bevy::prelude::*;
+    /// #[derive(Asset, TypePath, serde::Deserialize)]
+    /// struct Level {
+    ///     walls: Vec<i32>,
+    /// }
+    ///
+    /// let assets = AssetManager::new().add_data::<Level, _>("level", "first.level.ron");
+    /// ```
+    pub fn add_data<T, S: ToString>(mut self, tag: S, filename: S) -> anyhow::Result<Self>
+    where
+        T: Asset + DeserializeOwned,
+    {
+        let registration = DataRegistration::new::<T>(&filename.to_string());
+        if !registration.extension.contains('.') {
+            return Err(anyhow::Error::msg(format!(
+                "{} needs a compound extension that names its data type, such as first.level.ron",
+                filename.to_string()
+            )));
+        }
+        self.add_data_loader(registration)?;
+        self.add_asset(tag, filename, AssetType::Data)
+    }
+
+    /// Registers assets that are only loaded while the game is in a state
+    /// bound to `group`, rather than at startup. Bind groups to states with
+    /// [`GameStatePlugin::with_asset_group`](crate::GameStatePlugin::with_asset_group).
+    ///
+    /// ```no_run
+    /// # use my_library::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let assets = AssetManager::new()
+    ///     .add_image("wall", "wall.png")?
+    ///     .with_group("level2", |group| {
+    ///         group
+    ///             .add_image("boss", "boss.png")?
+    ///             .add_music("boss_theme", "boss.ogg")
+    ///     })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_group<S, F>(mut self, group: S, add_assets: F) -> anyhow::Result<Self>
+    where
+        S: ToString,
+        F: FnOnce(AssetManager) -> anyhow::Result<AssetManager>,
+    {
+        let group = group.to_string();
+        // The group finds its files in the same places the rest of the
+        // game's assets do.
+        let group_assets = AssetManager {
+            asset_folder: self.asset_folder.clone(),
+            embedded: self.embedded.clone(),
+            data_loaders: self.data_loaders.clone(),
+            ..AssetManager::empty()
+        };
+        let assets = add_assets(group_assets).with_context(|| format!("in asset group {group}"))?;
+        for mut asset in assets.asset_list {
+            asset.group = Some(group.clone());
+            self.asset_list.push(asset);
+        }
+        for loader in assets.data_loaders {
+            self.add_data_loader(loader)?;
+        }
+        self.fallbacks.extend(assets.fallbacks);
+        self.packs.extend(assets.packs);
+        self.embedded.extend(assets.embedded);
         Ok(self)
     }
 
     pub fn add_sprite_sheet<S: ToString>(
-        mut self,
+        self,
         tag: S,
         filename: S,
         sprite_width: f32,
@@ -63,27 +339,206 @@ impl AssetManager {
         sprites_x: usize,
         sprites_y: usize,
     ) -> anyhow::Result<Self> {
-        let filename = filename.to_string();
-        AssetManager::asset_exists(&filename)?;
-        self.asset_list.push((
-            tag.to_string(),
+        self.add_asset(
+            tag,
             filename,
             AssetType::SpriteSheet {
                 tile_size: Vec2::new(sprite_width, sprite_height),
                 sprites_x,
                 sprites_y,
+                padding: None,
+                offset: None,
             },
-        ));
+        )
+    }
+
+    /// Adds a grid sprite sheet with `padding` pixels between sprites and
+    /// an `offset` before the first one, matching the arguments of
+    /// `TextureAtlas::from_grid`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_padded_sprite_sheet<S: ToString>(
+        self,
+        tag: S,
+        filename: S,
+        sprite_width: f32,
+        sprite_height: f32,
+        sprites_x: usize,
+        sprites_y: usize,
+        padding: Vec2,
+        offset: Vec2,
+    ) -> anyhow::Result<Self> {
+        self.add_asset(
+            tag,
+            filename,
+            AssetType::SpriteSheet {
+                tile_size: Vec2::new(sprite_width, sprite_height),
+                sprites_x,
+                sprites_y,
+                padding: Some(padding),
+                offset: Some(offset),
+            },
+        )
+    }
+
+    /// Adds a sprite sheet from the JSON that TexturePacker or Aseprite
+    /// exports alongside the image, so sprites can be any size and
+    /// anywhere in the image. Sprites are numbered in the order the JSON
+    /// lists them; [`AssetStore::get_frame_index`] finds one by name.
+    pub fn add_packed_sprite_sheet<S: ToString>(self, tag: S, metadata: S) -> anyhow::Result<Self> {
+        self.add_asset(tag, metadata, AssetType::PackedSpriteSheet)
+    }
+
+    /// Packs registered images into a single texture atlas, tagged `tag`,
+    /// once they have all loaded. Sprites drawn from the same atlas share a
+    /// texture, so Bevy can draw many of them without switching textures.
+    /// Spawn them with [`spawn_packed_image!`](crate::spawn_packed_image),
+    /// or look them up with [`AssetStore::get_packed_image`].
+    ///
+    /// The atlas is at most 2048 pixels square. Images in a group are
+    /// packed once the group has loaded.
+    ///
+    /// ```no_run
+    /// # use my_library::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let assets = AssetManager::new()
+    ///     .add_image("wall", "wall.png")?
+    ///     .add_image("dragon", "flappy_dragon.png")?
+    ///     .pack_images("sprites", ["wall", "dragon"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pack_images<S, I>(mut self, tag: S, images: I) -> anyhow::Result<Self>
+    where
+        S: ToString,
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        let tag = tag.to_string();
+        if self.asset_list.iter().any(|asset| asset.tag == tag)
+            || self.packs.iter().any(|pack| pack.tag == tag)
+        {
+            return Err(anyhow::Error::msg(format!(
+                "the tag {tag} is already in use"
+            )));
+        }
+        let images: Vec<String> = images.into_iter().map(|image| image.to_string()).collect();
+        for image in images.iter() {
+            if !self
+                .asset_list
+                .iter()
+                .any(|asset| asset.tag == *image && matches!(asset.asset_type, AssetType::Image))
+            {
+                return Err(anyhow::Error::msg(format!(
+                    "unable to pack {tag}: no image is registered with the tag {image}"
+                )));
+            }
+        }
+        self.packs.push(ImagePack { tag, images });
+        Ok(self)
+    }
+
+    /// Sets a file to load in place of `tag` if it fails to load, such as a
+    /// "missing texture" image. It is only used when the
+    /// [`GameStatePlugin`](crate::GameStatePlugin) handles load failures
+    /// with [`LoadFailure::Fallback`](crate::LoadFailure::Fallback) or
+    /// [`LoadFailure::Retry`](crate::LoadFailure::Retry).
+    pub fn with_fallback<S: ToString>(mut self, tag: S, filename: S) -> anyhow::Result<Self> {
+        let filename = filename.to_string();
+        self.asset_exists(&filename)?;
+        self.fallbacks.insert(tag.to_string(), filename);
+        Ok(self)
+    }
+
+    /// Checks that every tag in `tags` is registered, so that a tag enum
+    /// made with [`asset_tags!`](crate::asset_tags) and the manifest can't
+    /// drift apart unnoticed.
+    ///
+    /// ```no_run
+    /// # use my_library::*;
+    /// asset_tags! {
+    ///     enum GameAsset {
+    ///         Wall => "wall",
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let assets = AssetManager::from_manifest("manifest.ron")?.check_tags(GameAsset::ALL)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_tags<T: AssetTag>(self, tags: &[T]) -> anyhow::Result<Self> {
+        let missing: Vec<&str> = tags
+            .iter()
+            .map(|tag| tag.tag())
+            .filter(|tag| !self.asset_list.iter().any(|asset| asset.tag == *tag))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow::Error::msg(format!(
+                "no asset is registered with the tags {}",
+                missing.join(", ")
+            )));
+        }
         Ok(self)
     }
 
-    fn asset_exists(filename: &str) -> anyhow::Result<()> {
+    fn add_data_loader(&mut self, registration: DataRegistration) -> anyhow::Result<()> {
+        match self
+            .data_loaders
+            .iter()
+            .find(|loader| loader.extension == registration.extension)
+        {
+            Some(loader) if loader.type_id == registration.type_id => Ok(()),
+            Some(loader) => Err(anyhow::Error::msg(format!(
+                "the .{} extension is already used by {}, so it can't also load {}",
+                registration.extension, loader.type_name, registration.type_name
+            ))),
+            None => {
+                self.data_loaders.push(registration);
+                Ok(())
+            }
+        }
+    }
+
+    fn add_asset<S: ToString>(
+        mut self,
+        tag: S,
+        filename: S,
+        asset_type: AssetType,
+    ) -> anyhow::Result<Self> {
+        let mut filename = filename.to_string();
+        self.asset_exists(&filename)?;
+        let mut asset_type = asset_type;
+        if let AssetType::PackedSpriteSheet = asset_type {
+            // Read the packer's JSON now, and load the image it describes.
+            let json = self
+                .read_asset(&filename)
+                .with_context(|| format!("unable to read sprite sheet {filename}"))?;
+            let sheet = PackedSheet::parse(&filename, &json)
+                .with_context(|| format!("unable to parse sprite sheet {filename}"))?;
+            self.asset_exists(&sheet.image)?;
+            filename = sheet.image.clone();
+            asset_type = AssetType::PackedLayout(sheet);
+        }
+        self.asset_list
+            .push(AssetEntry::new(tag.to_string(), filename, asset_type));
+        Ok(self)
+    }
+
+    fn asset_path(&self, filename: &str) -> std::path::PathBuf {
+        #[cfg(not(target_arch = "wasm32"))]
+        let base_path = bevy::asset::io::file::FileAssetReader::get_base_path();
+        #[cfg(target_arch = "wasm32")]
+        let base_path = std::path::PathBuf::new();
+        base_path.join(&self.asset_folder).join(filename)
+    }
+
+    fn asset_exists(&self, filename: &str) -> anyhow::Result<()> {
+        if self.embedded.contains_key(filename) {
+            return Ok(());
+        }
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let current_directory = std::env::current_dir()?;
-            let assets = current_directory.join("assets");
-            let new_image = assets.join(filename);
-            if !new_image.exists() {
+            if !self.asset_path(filename).exists() {
                 return Err(anyhow::Error::msg(format!(
                     "{} not found in assets directory",
                     &filename
@@ -92,12 +547,97 @@ impl AssetManager {
         }
         Ok(())
     }
+
+    /// Reads a text file, such as a manifest, from the embedded files or
+    /// the assets directory.
+    fn read_asset(&self, filename: &str) -> anyhow::Result<String> {
+        match self.embedded.get(filename) {
+            Some(bytes) => Ok(std::str::from_utf8(bytes)?.to_string()),
+            None => Ok(std::fs::read_to_string(self.asset_path(filename))?),
+        }
+    }
+
+    /// The size of an asset file in bytes, used to weight the loading
+    /// screen's progress bar. Sizes can't be read on the web, so every
+    /// file that isn't embedded counts the same there.
+    fn asset_size(&self, filename: &str) -> u64 {
+        let size = match self.embedded.get(filename) {
+            Some(bytes) => Some(bytes.len() as u64),
+            None => self
+                .asset_path(filename)
+                .metadata()
+                .ok()
+                .map(|metadata| metadata.len()),
+        };
+        size.map_or(1, |size| size.max(1))
+    }
+}
+
+/// The contents of an asset manifest file.
+#[derive(Deserialize)]
+struct AssetManifest {
+    assets: Vec<ManifestEntry>,
+    #[serde(default)]
+    packs: Vec<ImagePack>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    tag: String,
+    filename: String,
+    asset_type: AssetType,
+    #[serde(default)]
+    group: Option<String>,
+}
+
+impl AssetManifest {
+    fn parse(manifest: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(manifest)?)
+    }
 }
 
 impl Plugin for AssetManager {
     fn build(&self, app: &mut bevy::prelude::App) {
+        if let Some(asset_plugin) = app.get_added_plugins::<AssetPlugin>().first() {
+            if asset_plugin.file_path != self.asset_folder {
+                warn!(
+                    "Assets were checked in {} but Bevy loads them from {}; \
+                     call AssetManager::with_asset_path first",
+                    self.asset_folder, asset_plugin.file_path
+                );
+            }
+        }
+        if !self.embedded.is_empty() {
+            match app.world.get_resource::<EmbeddedAssetRegistry>() {
+                Some(registry) => {
+                    for (filename, bytes) in self.embedded.iter() {
+                        registry.insert_asset(
+                            self.asset_path(filename),
+                            Path::new(filename),
+                            *bytes,
+                        );
+                    }
+                }
+                None => {
+                    warn!("Unable to embed assets: add the AssetPlugin before the AssetManager")
+                }
+            }
+        }
+        for loader in self.data_loaders.iter() {
+            loader.register(app);
+        }
         app.insert_resource(self.clone());
         app.add_systems(Startup, setup);
+        app.add_systems(Update, crate::rebuild_atlases);
+        app.init_resource::<crate::AudioMixer>();
+        app.add_systems(
+            Update,
+            (
+                crate::start_emitters,
+                crate::update_sounds,
+                crate::update_listener,
+            ),
+        );
     }
 }
 
@@ -106,44 +646,271 @@ fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
 ) {
-    let mut assets = AssetStore {
-        asset_index: bevy::utils::HashMap::new(),
-        atlases_to_build: Vec::new(),
-        atlases: bevy::utils::HashMap::new(),
-    };
-    asset_resource
+    let mut assets = AssetStore::default();
+    for filename in asset_resource
         .asset_list
         .iter()
-        .for_each(|(tag, filename, asset_type)| {
-            match asset_type {
-                AssetType::SpriteSheet {
-                    tile_size,
-                    sprites_x,
-                    sprites_y,
-                } => {
-                    //Sprite Sheets require that we load the image first, and defer
-                    //sheet creation to the loading menu - after the image has loaded.
-                    let image_handle = asset_server.load_untyped(filename);
-                    let base_tag = format!("{tag}_base");
-                    assets.asset_index.insert(base_tag.clone(), image_handle);
-
-                    //Now that its loaded, we store the future atlas in the asset store.
-                    assets.atlases_to_build.push(FutureAtlas {
-                        tag: tag.clone(),
-                        texture_tag: base_tag,
-                        tile_size: *tile_size,
-                        sprites_x: *sprites_x,
-                        sprites_y: *sprites_y,
-                    });
-                }
-                _ => {
-                    //Most asset types don't require a separate loaded
-                    assets
-                        .asset_index
-                        .insert(tag.clone(), asset_server.load_untyped(filename));
-                }
-            }
-        });
+        .map(|asset| &asset.filename)
+        .chain(asset_resource.fallbacks.values())
+    {
+        if asset_resource.embedded.contains_key(filename) {
+            assets.embedded.insert(filename.clone());
+        }
+        assets
+            .file_sizes
+            .insert(filename.clone(), asset_resource.asset_size(filename));
+    }
+    for asset in asset_resource.asset_list.iter() {
+        if let Some(fallback) = asset_resource.fallbacks.get(&asset.tag) {
+            assets.fallbacks.insert(asset.index_tag(), fallback.clone());
+        }
+        match &asset.group {
+            // Grouped assets wait until a state that needs them is entered.
+            Some(group) => assets
+                .groups
+                .entry(group.clone())
+                .or_default()
+                .push(asset.clone()),
+            None => assets.load_asset(&asset_server, asset),
+        }
+    }
+    assets.packs = asset_resource.packs.clone();
     commands.remove_resource::<AssetManager>();
     commands.insert_resource(assets);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = AssetManifest::parse(
+            r#"(
+                assets: [
+                    (tag: "wall", filename: "wall.png", asset_type: Image),
+                    (tag: "flap", filename: "dragonflap.ogg", asset_type: Sound),
+                    (
+                        tag: "flappy",
+                        filename: "flappy_sprite_sheet.png",
+                        asset_type: SpriteSheet(tile_size: (62.0, 65.0), sprites_x: 4, sprites_y: 1),
+                    ),
+                ],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.assets.len(), 3);
+        assert_eq!(manifest.assets[0].tag, "wall");
+        assert!(matches!(manifest.assets[1].asset_type, AssetType::Sound));
+        match manifest.assets[2].asset_type {
+            AssetType::SpriteSheet {
+                tile_size,
+                sprites_x,
+                sprites_y,
+                padding,
+                offset,
+            } => {
+                assert_eq!(tile_size, Vec2::new(62.0, 65.0));
+                assert_eq!((sprites_x, sprites_y), (4, 1));
+                assert_eq!((padding, offset), (None, None));
+            }
+            _ => panic!("expected a sprite sheet"),
+        }
+    }
+
+    #[test]
+    fn test_parse_manifest_sprite_sheets() {
+        let manifest = AssetManifest::parse(
+            r#"(
+                assets: [
+                    (
+                        tag: "flappy",
+                        filename: "flappy_sprite_sheet.png",
+                        asset_type: SpriteSheet(
+                            tile_size: (62.0, 65.0), sprites_x: 4, sprites_y: 1,
+                            padding: Some((2.0, 0.0)), offset: Some((1.0, 0.0)),
+                        ),
+                    ),
+                    (tag: "hero", filename: "hero.json", asset_type: PackedSpriteSheet),
+                ],
+            )"#,
+        )
+        .unwrap();
+        match manifest.assets[0].asset_type {
+            AssetType::SpriteSheet {
+                padding, offset, ..
+            } => {
+                assert_eq!(padding, Some(Vec2::new(2.0, 0.0)));
+                assert_eq!(offset, Some(Vec2::new(1.0, 0.0)));
+            }
+            _ => panic!("expected a sprite sheet"),
+        }
+        assert!(matches!(
+            manifest.assets[1].asset_type,
+            AssetType::PackedSpriteSheet
+        ));
+    }
+
+    #[test]
+    fn test_parse_manifest_groups() {
+        let manifest = AssetManifest::parse(
+            r#"(
+                assets: [
+                    (tag: "wall", filename: "wall.png", asset_type: Image),
+                    (tag: "boss", filename: "boss.png", asset_type: Image, group: Some("level2")),
+                ],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.assets[0].group, None);
+        assert_eq!(manifest.assets[1].group.as_deref(), Some("level2"));
+    }
+
+    #[test]
+    fn test_pack_images() {
+        let manifest = AssetManifest::parse(
+            r#"(
+                assets: [],
+                packs: [(tag: "menus", images: ["main_menu", "game_over"])],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.packs[0].images, ["main_menu", "game_over"]);
+
+        let assets = AssetManager::new()
+            .pack_images("menus", ["main_menu", "game_over"])
+            .unwrap();
+        assert_eq!(assets.packs.len(), 1);
+        let error = assets
+            .clone()
+            .pack_images("menus", ["main_menu"])
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "the tag menus is already in use");
+        let error = assets.pack_images("more", ["wall"]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "unable to pack more: no image is registered with the tag wall"
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        assert!(AssetManifest::parse("(assets: [(tag: \"wall\")])").is_err());
+        assert!(AssetManifest::parse(
+            "(assets: [(tag: \"a\", filename: \"a.png\", asset_type: Model)])"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_tags() {
+        let assets = AssetManager::new();
+        assert!(assets
+            .clone()
+            .check_tags(&["main_menu", "game_over"])
+            .is_ok());
+        let error = assets
+            .check_tags(&["main_menu", "wal", "flapy"])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "no asset is registered with the tags wal, flapy"
+        );
+    }
+
+    #[test]
+    fn test_embedded_assets() {
+        let manifest = br#"(
+            assets: [(tag: "wall", filename: "levels/wall.png", asset_type: Image)],
+        )"#;
+        let assets = AssetManager::empty()
+            .with_embedded(&[
+                ("levels/manifest.ron", manifest as &[u8]),
+                ("levels/wall.png", &[0; 16]),
+            ])
+            .add_manifest("levels/manifest.ron")
+            .unwrap();
+        assert_eq!(assets.asset_list[0].filename, "levels/wall.png");
+        assert_eq!(assets.asset_size("levels/wall.png"), 16);
+        assert!(assets.add_image("boss", "levels/boss.png").is_err());
+    }
+
+    #[test]
+    fn test_group_uses_parent_files() {
+        let assets = AssetManager::empty()
+            .with_asset_path("data")
+            .with_embedded(&[("boss.png", &[0; 16])])
+            .with_group("level2", |group| {
+                assert_eq!(group.asset_folder, "data");
+                group.add_image("boss", "boss.png")
+            })
+            .unwrap();
+        assert_eq!(assets.asset_list[0].group.as_deref(), Some("level2"));
+        assert!(AssetManager::empty()
+            .with_group("level2", |group| group.add_image("boss", "boss.png"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_asset_path() {
+        let assets = AssetManager::empty().with_asset_path("data");
+        assert_eq!(
+            assets.asset_path("wall.png"),
+            bevy::asset::io::file::FileAssetReader::get_base_path().join("data/wall.png")
+        );
+    }
+
+    #[test]
+    fn test_add_data() {
+        #[derive(Asset, bevy::reflect::TypePath, serde::Deserialize)]
+        struct Level;
+        #[derive(Asset, bevy::reflect::TypePath, serde::Deserialize)]
+        struct Wave;
+
+        let assets = AssetManager::empty().with_embedded(&[
+            ("first.level.ron", b"()" as &[u8]),
+            ("second.level.ron", b"()"),
+            ("swarm.level.ron", b"()"),
+            ("swarm.wave.ron", b"()"),
+            ("plain.ron", b"()"),
+        ]);
+        let assets = assets
+            .add_data::<Level, _>("first", "first.level.ron")
+            .unwrap()
+            .add_data::<Level, _>("second", "second.level.ron")
+            .unwrap()
+            .add_data::<Wave, _>("swarm", "swarm.wave.ron")
+            .unwrap();
+        assert_eq!(assets.data_loaders.len(), 2);
+
+        let error = assets
+            .clone()
+            .add_data::<Wave, _>("wave", "swarm.level.ron")
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("the .level.ron extension is already used by"));
+        let error = assets
+            .add_data::<Level, _>("plain", "plain.ron")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "plain.ron needs a compound extension that names its data type, such as first.level.ron"
+        );
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let error = AssetManager::from_manifest("no_such_manifest.ron")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "no_such_manifest.ron not found in assets directory"
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use crate::{
+    pack_images, spawn_sound, AssetEntry, AssetLoadFailure, AssetTag, AssetType, AtlasLayout,
+    AudioChannel, ImagePack, SoundHandle, SoundOptions,
+};
 use bevy::{
     asset::{Asset, LoadedUntypedAsset},
     prelude::*,
-    utils::HashMap,
+    utils::{HashMap, HashSet},
 };
 
 pub type LoadedAssets = Assets<LoadedUntypedAsset>;
@@ -10,45 +14,511 @@ pub type AssetResource<'w> = Res<'w, LoadedAssets>;
 pub(crate) struct FutureAtlas {
     pub(crate) tag: String,
     pub(crate) texture_tag: String,
-    pub(crate) tile_size: Vec2,
-    pub(crate) sprites_x: usize,
-    pub(crate) sprites_y: usize,
+    pub(crate) layout: AtlasLayout,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct AssetStore {
     pub(crate) asset_index: HashMap<String, Handle<LoadedUntypedAsset>>,
     pub(crate) atlases_to_build: Vec<FutureAtlas>,
     pub(crate) atlases: HashMap<String, Handle<TextureAtlas>>,
+    /// How each built atlas was made, so it can be rebuilt when its image
+    /// is reloaded.
+    pub(crate) atlas_sources: HashMap<String, FutureAtlas>,
+    /// The file each tag in `asset_index` was loaded from.
+    pub(crate) files: HashMap<String, String>,
+    /// Files to load in place of a tag that fails to load.
+    pub(crate) fallbacks: HashMap<String, String>,
+    /// Tags that failed to load and were replaced by Bevy's default handle.
+    pub(crate) placeholders: HashSet<String>,
+    pub(crate) failures: Vec<AssetLoadFailure>,
+    /// Assets registered with a group, by group name.
+    pub(crate) groups: HashMap<String, Vec<AssetEntry>>,
+    pub(crate) loaded_groups: HashSet<String>,
+    /// Images to pack into shared atlases once they have loaded.
+    pub(crate) packs: Vec<ImagePack>,
+    /// The atlas tag and index of each packed image, by image tag.
+    pub(crate) packed_images: HashMap<String, (String, usize)>,
+    /// Files built into the executable with
+    /// [`AssetManager::with_embedded`](crate::AssetManager::with_embedded).
+    pub(crate) embedded: HashSet<String>,
+    pub(crate) file_sizes: HashMap<String, u64>,
 }
 
 impl AssetStore {
-    pub fn get_handle<T>(&self, index: &str, assets: &LoadedAssets) -> Option<Handle<T>>
+    /// Starts loading an asset. Sprite sheets load their image now and
+    /// build the atlas once the loading screen sees it arrive.
+    pub(crate) fn load_asset(&mut self, asset_server: &AssetServer, asset: &AssetEntry) {
+        let index_tag = asset.index_tag();
+        self.asset_index.insert(
+            index_tag.clone(),
+            asset_server.load_untyped(self.load_path(&asset.filename)),
+        );
+        self.files.insert(index_tag.clone(), asset.filename.clone());
+        self.placeholders.remove(&index_tag);
+        let layout = match &asset.asset_type {
+            AssetType::SpriteSheet {
+                tile_size,
+                sprites_x,
+                sprites_y,
+                padding,
+                offset,
+            } => AtlasLayout::Grid {
+                tile_size: *tile_size,
+                sprites_x: *sprites_x,
+                sprites_y: *sprites_y,
+                padding: *padding,
+                offset: *offset,
+            },
+            AssetType::PackedLayout(sheet) => AtlasLayout::Packed(sheet.clone()),
+            _ => return,
+        };
+        self.atlases_to_build.push(FutureAtlas {
+            tag: asset.tag.clone(),
+            texture_tag: index_tag,
+            layout,
+        });
+    }
+
+    /// The path Bevy loads `filename` from: the `embedded` source for
+    /// embedded files, or else the assets directory.
+    pub(crate) fn load_path(&self, filename: &str) -> String {
+        if self.embedded.contains(filename) {
+            format!("embedded://{filename}")
+        } else {
+            filename.to_string()
+        }
+    }
+
+    /// The size of a file in bytes, or 1 if it isn't known.
+    pub(crate) fn file_size(&self, filename: &str) -> u64 {
+        self.file_sizes.get(filename).copied().unwrap_or(1)
+    }
+
+    /// Starts loading every asset in `group`. Returns false if there is no
+    /// such group.
+    pub(crate) fn load_group(&mut self, asset_server: &AssetServer, group: &str) -> bool {
+        let Some(assets) = self.groups.get(group).cloned() else {
+            return false;
+        };
+        for asset in assets.iter() {
+            self.load_asset(asset_server, asset);
+        }
+        self.loaded_groups.insert(group.to_string());
+        true
+    }
+
+    /// Drops the store's handles to every asset in `group`. Bevy frees each
+    /// asset once nothing else holds a handle to it.
+    pub(crate) fn unload_group(&mut self, group: &str) {
+        if !self.loaded_groups.remove(group) {
+            return;
+        }
+        for asset in self.groups.get(group).into_iter().flatten() {
+            let index_tag = asset.index_tag();
+            self.asset_index.remove(&index_tag);
+            self.files.remove(&index_tag);
+            self.placeholders.remove(&index_tag);
+            self.atlases.remove(&asset.tag);
+            self.atlas_sources.remove(&asset.tag);
+            // A pack is rebuilt when all of its images are loaded again.
+            if let Some((pack, _)) = self.packed_images.remove(&asset.tag) {
+                self.atlases.remove(&pack);
+                self.packed_images.retain(|_, (other, _)| *other != pack);
+            }
+        }
+    }
+
+    /// Packs the images of each pack that hasn't been built yet, or that
+    /// `rebuild` selects by its image handles, once all of its images have
+    /// loaded. Rebuilt atlases keep their handle.
+    pub(crate) fn build_packs(
+        &mut self,
+        loaded_assets: &LoadedAssets,
+        images: &mut Assets<Image>,
+        texture_atlases: &mut Assets<TextureAtlas>,
+        rebuild: impl Fn(&[(String, Handle<Image>)]) -> bool,
+    ) {
+        let packs = std::mem::take(&mut self.packs);
+        for pack in packs.iter() {
+            // Packs with an image in a group that isn't loaded wait for it.
+            let Some(to_pack) = pack
+                .images
+                .iter()
+                .map(|tag| Some((tag.clone(), self.get_image(tag, loaded_assets)?)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let existing = self.atlases.get(&pack.tag).cloned();
+            if existing.is_some() && !rebuild(&to_pack) {
+                continue;
+            }
+            match pack_images(&to_pack, images) {
+                Ok((atlas, indices)) => {
+                    let atlas_handle = match existing {
+                        Some(atlas_handle) => {
+                            texture_atlases.insert(atlas_handle.id(), atlas);
+                            atlas_handle
+                        }
+                        None => texture_atlases.add(atlas),
+                    };
+                    self.atlases.insert(pack.tag.clone(), atlas_handle);
+                    for (tag, index) in pack.images.iter().zip(indices) {
+                        self.packed_images
+                            .insert(tag.clone(), (pack.tag.clone(), index));
+                    }
+                }
+                Err(error) => warn!("Unable to pack {}: {error}", pack.tag),
+            }
+        }
+        self.packs = packs;
+    }
+
+    /// True if the assets in `group` are loaded, or being loaded.
+    pub fn is_group_loaded(&self, group: &str) -> bool {
+        self.loaded_groups.contains(group)
+    }
+
+    /// Finds the handle for `index`, or explains why it can't: the tag was
+    /// never registered, or the asset hasn't finished loading.
+    pub fn try_get_handle<T>(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<T>>
     where
         T: Asset,
     {
-        if let Some(handle_untyped) = self.asset_index.get(index) {
-            if let Some(handle) = assets.get(handle_untyped) {
-                return Some(handle.handle.clone().typed::<T>());
+        let index = index.tag();
+        if self.placeholders.contains(index) {
+            return Ok(Handle::default());
+        }
+        let Some(handle_untyped) = self.asset_index.get(index) else {
+            return Err(anyhow::Error::msg(format!(
+                "no asset is registered with the tag {index}"
+            )));
+        };
+        match assets.get(handle_untyped) {
+            Some(handle) => Ok(handle.handle.clone().typed::<T>()),
+            None => Err(anyhow::Error::msg(format!(
+                "the asset {index} hasn't finished loading"
+            ))),
+        }
+    }
+
+    pub fn try_get_image(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<Image>> {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_sound(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<AudioSource>> {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_font(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<Font>> {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_shader(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<Shader>> {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_music(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<AudioSource>> {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_data<T>(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> anyhow::Result<Handle<T>>
+    where
+        T: Asset,
+    {
+        self.try_get_handle(index, assets)
+    }
+
+    pub fn try_get_atlas_handle(
+        &self,
+        index: impl AssetTag,
+    ) -> anyhow::Result<Handle<TextureAtlas>> {
+        let index = index.tag();
+        self.atlases.get(index).cloned().ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "no sprite sheet is registered with the tag {index}"
+            ))
+        })
+    }
+
+    /// Finds the atlas an image was packed into with
+    /// [`AssetManager::pack_images`](crate::AssetManager::pack_images), and
+    /// the image's index in it.
+    pub fn try_get_packed_image(
+        &self,
+        index: impl AssetTag,
+    ) -> anyhow::Result<(Handle<TextureAtlas>, usize)> {
+        let index = index.tag();
+        let Some((pack, sprite_index)) = self.packed_images.get(index) else {
+            return Err(anyhow::Error::msg(format!(
+                "no packed atlas holds the image {index}"
+            )));
+        };
+        Ok((self.try_get_atlas_handle(pack)?, *sprite_index))
+    }
+
+    pub fn get_handle<T>(&self, index: impl AssetTag, assets: &LoadedAssets) -> Option<Handle<T>>
+    where
+        T: Asset,
+    {
+        self.try_get_handle(index, assets).ok()
+    }
+
+    pub fn get_image(&self, index: impl AssetTag, assets: &LoadedAssets) -> Option<Handle<Image>> {
+        self.get_handle(index, assets)
+    }
+
+    pub fn get_sound(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> Option<Handle<AudioSource>> {
+        self.get_handle(index, assets)
+    }
+
+    pub fn get_font(&self, index: impl AssetTag, assets: &LoadedAssets) -> Option<Handle<Font>> {
+        self.get_handle(index, assets)
+    }
+
+    pub fn get_shader(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> Option<Handle<Shader>> {
+        self.get_handle(index, assets)
+    }
+
+    pub fn get_music(
+        &self,
+        index: impl AssetTag,
+        assets: &LoadedAssets,
+    ) -> Option<Handle<AudioSource>> {
+        self.get_handle(index, assets)
+    }
+
+    /// Finds the handle of a data file added with
+    /// [`AssetManager::add_data`](crate::AssetManager::add_data). Read the
+    /// value itself from `Res<Assets<T>>`.
+    pub fn get_data<T>(&self, index: impl AssetTag, assets: &LoadedAssets) -> Option<Handle<T>>
+    where
+        T: Asset,
+    {
+        self.get_handle(index, assets)
+    }
+
+    pub fn get_atlas_handle(&self, index: impl AssetTag) -> Option<Handle<TextureAtlas>> {
+        self.try_get_atlas_handle(index).ok()
+    }
+
+    pub fn get_packed_image(&self, index: impl AssetTag) -> Option<(Handle<TextureAtlas>, usize)> {
+        self.try_get_packed_image(index).ok()
+    }
+
+    /// Plays a sound once on the [`AudioChannel::Sfx`] channel. If the
+    /// sound can't be found, the problem is logged and nothing plays.
+    pub fn play(
+        &self,
+        sound_name: impl AssetTag,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+    ) -> Option<SoundHandle> {
+        self.play_with(sound_name, SoundOptions::default(), commands, assets)
+    }
+
+    /// Plays a sound once from `position`, panned and attenuated relative
+    /// to the active camera. See
+    /// [`AudioMixer::with_hearing_distance`](crate::AudioMixer::with_hearing_distance).
+    pub fn play_at(
+        &self,
+        sound_name: impl AssetTag,
+        position: Vec2,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+    ) -> Option<SoundHandle> {
+        self.play_with(
+            sound_name,
+            SoundOptions::default().at(position),
+            commands,
+            assets,
+        )
+    }
+
+    /// Plays a sound with a channel, volume, speed, looping or position set
+    /// in `options`. The handle stops or fades it out.
+    pub fn play_with(
+        &self,
+        sound_name: impl AssetTag,
+        options: SoundOptions,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+    ) -> Option<SoundHandle> {
+        match self.try_get_sound(&sound_name, assets) {
+            Ok(source) => Some(spawn_sound(
+                commands,
+                sound_name.tag(),
+                source,
+                options,
+                false,
+                None,
+            )),
+            Err(error) => {
+                warn!("Unable to play {}: {error}", sound_name.tag());
+                None
             }
-            None
-        } else {
-            None
         }
     }
 
-    pub fn play(&self, sound_name: &str, commands: &mut Commands, assets: &LoadedAssets) {
-        let sound_handle: Handle<AudioSource> = self.get_handle(sound_name, assets).unwrap();
-        commands.spawn(AudioBundle {
-            source: sound_handle,
-            ..default()
-        });
+    /// Starts a music track on the [`AudioChannel::Music`] channel, looping
+    /// until it is stopped. Any other track fades out as this one fades in;
+    /// if the track is already playing, it carries on. If the track can't
+    /// be found, the problem is logged and nothing plays.
+    pub fn play_music(
+        &self,
+        music_name: impl AssetTag,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+    ) {
+        match self.try_get_music(&music_name, assets) {
+            Ok(source) => {
+                spawn_sound(
+                    commands,
+                    music_name.tag(),
+                    source,
+                    SoundOptions::on(AudioChannel::Music).looped(),
+                    true,
+                    None,
+                );
+            }
+            Err(error) => warn!("Unable to play {}: {error}", music_name.tag()),
+        }
+    }
+
+    /// The index of a named sprite in a sheet added with
+    /// [`AssetManager::add_packed_sprite_sheet`](crate::AssetManager::add_packed_sprite_sheet),
+    /// such as `"flappy 2.aseprite"`.
+    pub fn get_frame_index(&self, sheet: impl AssetTag, frame: &str) -> Option<usize> {
+        self.atlas_sources
+            .get(sheet.tag())?
+            .layout
+            .frame_index(frame)
+    }
+
+    /// Every asset that failed to load, in the order the failures were
+    /// found.
+    pub fn failures(&self) -> &[AssetLoadFailure] {
+        &self.failures
+    }
+
+    /// True if `index` failed to load and had no fallback file, so its
+    /// handle is Bevy's default placeholder---a white square for images.
+    pub fn is_placeholder(&self, index: impl AssetTag) -> bool {
+        self.placeholders.contains(index.tag())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_errors_name_the_tag() {
+        let store = AssetStore::default();
+        let loaded_assets = LoadedAssets::default();
+        let error = store
+            .try_get_image("wal", &loaded_assets)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "no asset is registered with the tag wal");
+        let error = store.try_get_atlas_handle("flapy").unwrap_err().to_string();
+        assert_eq!(error, "no sprite sheet is registered with the tag flapy");
+        let error = store.try_get_packed_image("wal").unwrap_err().to_string();
+        assert_eq!(error, "no packed atlas holds the image wal");
+    }
+
+    #[test]
+    fn test_placeholder_handles() {
+        let mut store = AssetStore::default();
+        let loaded_assets = LoadedAssets::default();
+        assert_eq!(store.get_image("wall", &loaded_assets), None);
+
+        store.placeholders.insert("wall".to_string());
+        assert!(store.is_placeholder("wall"));
+        assert_eq!(
+            store.get_image("wall", &loaded_assets),
+            Some(Handle::default())
+        );
     }
 
-    pub fn get_atlas_handle(&self, index: &str) -> Option<Handle<TextureAtlas>> {
-        if let Some(handle) = self.atlases.get(index) {
-            return Some(handle.clone());
+    #[test]
+    fn test_build_packs() {
+        let mut store = AssetStore::default();
+        let mut loaded_assets = LoadedAssets::default();
+        let mut images = Assets::<Image>::default();
+        let mut texture_atlases = Assets::<TextureAtlas>::default();
+        for tag in ["wall", "dragon"] {
+            let image = images.add(Image::default());
+            let loaded = loaded_assets.add(LoadedUntypedAsset {
+                handle: image.untyped(),
+            });
+            store.asset_index.insert(tag.to_string(), loaded);
         }
-        None
+        store.packs.push(ImagePack {
+            tag: "sprites".to_string(),
+            images: vec!["wall".to_string(), "dragon".to_string(), "boss".to_string()],
+        });
+
+        // The boss hasn't loaded, so nothing is packed yet.
+        store.build_packs(&loaded_assets, &mut images, &mut texture_atlases, |_| false);
+        assert!(store.get_packed_image("wall").is_none());
+
+        store.packs[0].images.pop();
+        store.build_packs(&loaded_assets, &mut images, &mut texture_atlases, |_| false);
+        let (atlas, wall) = store.try_get_packed_image("wall").unwrap();
+        let (_, dragon) = store.try_get_packed_image("dragon").unwrap();
+        assert_ne!(wall, dragon);
+        assert_eq!(texture_atlases.get(&atlas).unwrap().len(), 2);
+
+        store.groups.insert(
+            "level".to_string(),
+            vec![AssetEntry {
+                tag: "wall".to_string(),
+                filename: "wall.png".to_string(),
+                asset_type: AssetType::Image,
+                group: Some("level".to_string()),
+            }],
+        );
+        store.loaded_groups.insert("level".to_string());
+        store.unload_group("level");
+        assert!(store.get_packed_image("dragon").is_none());
+        assert!(store.get_atlas_handle("sprites").is_none());
     }
 }
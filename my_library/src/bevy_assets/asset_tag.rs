@@ -0,0 +1,139 @@
+/// Names an asset in the [`AssetStore`](crate::AssetStore). Plain strings
+/// work, but an enum made with [`asset_tags!`](crate::asset_tags) lets the
+/// compiler catch typos.
+pub trait AssetTag {
+    fn tag(&self) -> &str;
+}
+
+impl AssetTag for str {
+    fn tag(&self) -> &str {
+        self
+    }
+}
+
+impl AssetTag for String {
+    fn tag(&self) -> &str {
+        self
+    }
+}
+
+impl<T: AssetTag + ?Sized> AssetTag for &T {
+    fn tag(&self) -> &str {
+        (**self).tag()
+    }
+}
+
+/// Generates an enum of asset tags, one variant per tag, that can be passed
+/// anywhere the [`AssetStore`](crate::AssetStore) takes a tag. `ALL` lists
+/// every variant.
+///
+/// Generate the enum from a manifest in the `assets` directory beside your
+/// `Cargo.toml`, and a tag missing from the manifest won't compile. Each
+/// tag becomes an UpperCamelCase variant (`bg_far` becomes `BgFar`), and
+/// the enum is rebuilt when the manifest changes:
+///
+/// ```ignore
+/// use my_library::*;
+///
+/// asset_tags! {
+///     pub enum GameAsset from "manifest.ron"
+/// }
+/// ```
+///
+/// Or list the tags yourself, and check them against the manifest at
+/// startup with [`AssetManager::check_tags`](crate::AssetManager::check_tags):
+///
+/// ```
+/// use my_library::*;
+///
+/// asset_tags! {
+///     pub enum GameAsset {
+///         Wall => "wall",
+///         Flap => "flap",
+///     }
+/// }
+///
+/// assert_eq!(GameAsset::Wall.tag(), "wall");
+/// assert_eq!(GameAsset::ALL, &[GameAsset::Wall, GameAsset::Flap]);
+/// ```
+#[macro_export]
+macro_rules! asset_tags {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident from $manifest:literal
+    ) => {
+        $crate::asset_tags_from_manifest!($crate, $(#[$meta])* $vis enum $name from $manifest);
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident => $tag:literal),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            /// Every tag, in declaration order.
+            pub const ALL: &'static [$name] = &[$($name::$variant),*];
+        }
+
+        impl $crate::AssetTag for $name {
+            fn tag(&self) -> &str {
+                match self {
+                    $($name::$variant => $tag),*
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str($crate::AssetTag::tag(self))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    crate::asset_tags! {
+        enum TestAsset {
+            Wall => "wall",
+            Dragon => "dragon",
+        }
+    }
+
+    crate::asset_tags! {
+        enum ManifestAsset from "test_manifest.ron"
+    }
+
+    fn tag_of(tag: impl AssetTag) -> String {
+        tag.tag().to_string()
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(tag_of("wall"), "wall");
+        assert_eq!(tag_of(String::from("wall")), "wall");
+        let owned = String::from("wall");
+        assert_eq!(tag_of(&owned), "wall");
+        assert_eq!(owned, "wall");
+        assert_eq!(tag_of(TestAsset::Dragon), "dragon");
+        assert_eq!(TestAsset::ALL, &[TestAsset::Wall, TestAsset::Dragon]);
+        assert_eq!(TestAsset::Wall.to_string(), "wall");
+        assert_eq!(
+            ManifestAsset::ALL,
+            &[
+                ManifestAsset::Wall,
+                ManifestAsset::BgFar,
+                ManifestAsset::Sprites
+            ]
+        );
+        assert_eq!(ManifestAsset::BgFar.tag(), "bg_far");
+    }
+}
@@ -0,0 +1,683 @@
+//! Sound playback through mixer channels, with per-channel volume, limits
+//! on how many copies of a sound overlap, and fades. Sounds given a
+//! position are panned and attenuated relative to the active camera.
+use crate::{AssetStore, AssetTag, LoadedAssets, PhysicsPosition};
+use bevy::{
+    audio::{GlobalVolume, PlaybackMode, SpatialScale, Volume},
+    prelude::*,
+    utils::HashMap,
+};
+
+/// A group of sounds whose volume is set together, such as from an options
+/// menu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AudioChannel {
+    /// Sound effects. Sounds play here unless told otherwise.
+    #[default]
+    Sfx,
+    /// Background music, which [`AssetStore::play_music`](crate::AssetStore::play_music)
+    /// crossfades.
+    Music,
+    /// Menu clicks and other interface sounds.
+    Ui,
+}
+
+impl AudioChannel {
+    /// Fades out every sound playing on the channel over `seconds`, then
+    /// stops them.
+    pub fn fade_out(self, commands: &mut Commands, seconds: f32) {
+        commands.add(move |world: &mut World| {
+            let mut sounds = world.query::<&mut PlayingSound>();
+            for mut sound in sounds.iter_mut(world) {
+                if sound.channel == self {
+                    sound.fade_out(seconds);
+                }
+            }
+        });
+    }
+
+    /// Stops every sound playing on the channel.
+    pub fn stop(self, commands: &mut Commands) {
+        self.fade_out(commands, 0.0);
+    }
+}
+
+/// Channel volumes and playback limits. The [`AssetManager`](crate::AssetManager)
+/// adds a default mixer; insert your own before it to change the defaults,
+/// and change volumes at any time through `ResMut<AudioMixer>`.
+///
+/// ```no_run
+/// # use my_library::*;
+/// # use bevy::prelude::*;
+/// App::new().insert_resource(
+///     AudioMixer::default()
+///         .with_volume(AudioChannel::Music, 0.5)
+///         .with_max_instances("flap", 2),
+/// );
+/// ```
+#[derive(Resource, Clone, Debug)]
+pub struct AudioMixer {
+    volumes: HashMap<AudioChannel, f32>,
+    max_instances: HashMap<String, usize>,
+    crossfade: f32,
+    hearing_distance: f32,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        Self {
+            volumes: HashMap::new(),
+            max_instances: HashMap::new(),
+            crossfade: 1.0,
+            hearing_distance: 1024.0,
+        }
+    }
+}
+
+impl AudioMixer {
+    /// Sets a channel's volume, where 1.0 is the sound's own volume.
+    pub fn with_volume(mut self, channel: AudioChannel, volume: f32) -> Self {
+        self.set_volume(channel, volume);
+        self
+    }
+
+    /// Limits how many copies of a sound can play at once. Once the limit
+    /// is reached, further requests to play it are ignored until a copy
+    /// finishes.
+    pub fn with_max_instances(mut self, tag: impl AssetTag, max: usize) -> Self {
+        self.max_instances.insert(tag.tag().to_string(), max);
+        self
+    }
+
+    /// Sets how long music takes to fade from one track to the next. The
+    /// default is one second.
+    pub fn with_crossfade(mut self, seconds: f32) -> Self {
+        self.crossfade = seconds;
+        self
+    }
+
+    /// Sets how sounds with a position are heard, in world units. The
+    /// camera's ears sit a quarter of this distance either side of it. A
+    /// sound within a quarter of the distance of an ear plays at full
+    /// volume, and fades with the square of the distance beyond that. The
+    /// default is 1024, so sounds level with the camera play at full volume
+    /// anywhere across a 1024 pixel wide screen.
+    pub fn with_hearing_distance(mut self, distance: f32) -> Self {
+        self.hearing_distance = distance.max(f32::EPSILON);
+        self
+    }
+
+    /// Sets a channel's volume. Sounds already playing on it change too.
+    pub fn set_volume(&mut self, channel: AudioChannel, volume: f32) {
+        self.volumes.insert(channel, volume.max(0.0));
+    }
+
+    pub fn volume(&self, channel: AudioChannel) -> f32 {
+        self.volumes.get(&channel).copied().unwrap_or(1.0)
+    }
+}
+
+/// How to play a sound with [`AssetStore::play_with`](crate::AssetStore::play_with).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundOptions {
+    pub channel: AudioChannel,
+    /// Volume, before the channel's volume is applied.
+    pub volume: f32,
+    /// Playback speed, which also changes the pitch.
+    pub speed: f32,
+    /// Repeats the sound until it is stopped.
+    pub looped: bool,
+    /// Where the sound comes from. Sounds without a position play at the
+    /// same volume in both speakers.
+    pub position: Option<Vec2>,
+}
+
+impl Default for SoundOptions {
+    fn default() -> Self {
+        Self {
+            channel: AudioChannel::Sfx,
+            volume: 1.0,
+            speed: 1.0,
+            looped: false,
+            position: None,
+        }
+    }
+}
+
+impl SoundOptions {
+    /// Plays once, on `channel`, at full volume and normal speed.
+    pub fn on(channel: AudioChannel) -> Self {
+        Self {
+            channel,
+            ..default()
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn looped(mut self) -> Self {
+        self.looped = true;
+        self
+    }
+
+    pub fn at(mut self, position: Vec2) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+/// Plays a looping sound from an entity's [`PhysicsPosition`] for as long
+/// as the entity has this component. The sound starts once it has loaded.
+///
+/// ```no_run
+/// # use my_library::*;
+/// # use bevy::prelude::*;
+/// fn spawn_engine(mut commands: Commands) {
+///     commands.spawn((
+///         PhysicsPosition::new(Vec2::ZERO),
+///         SoundEmitter::new("engine"),
+///     ));
+/// }
+/// ```
+#[derive(Component, Clone, Debug)]
+pub struct SoundEmitter {
+    tag: String,
+    options: SoundOptions,
+    sound: Option<SoundHandle>,
+}
+
+impl SoundEmitter {
+    pub fn new(tag: impl AssetTag) -> Self {
+        Self {
+            tag: tag.tag().to_string(),
+            options: SoundOptions::default().looped(),
+            sound: None,
+        }
+    }
+
+    /// Sets the channel, volume and speed. The sound always loops, and
+    /// follows the entity.
+    pub fn with_options(mut self, options: SoundOptions) -> Self {
+        self.options = SoundOptions {
+            looped: true,
+            ..options
+        };
+        self
+    }
+
+    /// The sound, once it has started.
+    pub fn sound(&self) -> Option<SoundHandle> {
+        self.sound
+    }
+}
+
+/// Refers to a sound started by the [`AssetStore`](crate::AssetStore).
+/// Stopping a sound that has already finished, or that wasn't played
+/// because of its instance limit, does nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoundHandle(Entity);
+
+impl SoundHandle {
+    /// The entity the sound plays from.
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+
+    pub fn stop(&self, commands: &mut Commands) {
+        self.fade_out(commands, 0.0);
+    }
+
+    /// Fades the sound out over `seconds`, then stops it.
+    pub fn fade_out(&self, commands: &mut Commands, seconds: f32) {
+        let entity = self.0;
+        commands.add(move |world: &mut World| {
+            if let Some(mut sound) = world.get_mut::<PlayingSound>(entity) {
+                sound.fade_out(seconds);
+            }
+        });
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Fade {
+    from: f32,
+    to: f32,
+    seconds: f32,
+    elapsed: f32,
+    /// Stop the sound once the fade ends.
+    stop: bool,
+}
+
+/// Marks a sound started by the [`AssetStore`](crate::AssetStore).
+#[derive(Component, Clone, Debug)]
+pub struct PlayingSound {
+    tag: String,
+    channel: AudioChannel,
+    volume: f32,
+    /// How far faded in the sound is, from 0.0 to 1.0.
+    gain: f32,
+    fade: Option<Fade>,
+    /// The [`SoundEmitter`] entity the sound follows.
+    emitter: Option<Entity>,
+}
+
+impl PlayingSound {
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn channel(&self) -> AudioChannel {
+        self.channel
+    }
+
+    fn fade_out(&mut self, seconds: f32) {
+        self.fade = Some(Fade {
+            from: self.gain,
+            to: 0.0,
+            seconds,
+            elapsed: 0.0,
+            stop: true,
+        });
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.fade.as_ref().is_some_and(|fade| fade.stop)
+    }
+
+    /// Moves any fade on by `delta` seconds. Returns false once a sound
+    /// that was fading out has gone silent.
+    fn advance(&mut self, delta: f32) -> bool {
+        let Some(fade) = &mut self.fade else {
+            return true;
+        };
+        fade.elapsed += delta;
+        let progress = if fade.seconds > 0.0 {
+            (fade.elapsed / fade.seconds).min(1.0)
+        } else {
+            1.0
+        };
+        self.gain = fade.from + (fade.to - fade.from) * progress;
+        if progress >= 1.0 {
+            let stop = fade.stop;
+            self.fade = None;
+            return !stop;
+        }
+        true
+    }
+
+    fn mixed_volume(&self, mixer: &AudioMixer) -> f32 {
+        mixer.volume(self.channel) * self.volume * self.gain
+    }
+}
+
+/// Reserves an entity for a sound, and starts it once commands are applied
+/// so that sounds requested in the same frame count towards each other's
+/// limits.
+pub(crate) fn spawn_sound(
+    commands: &mut Commands,
+    tag: &str,
+    source: Handle<AudioSource>,
+    options: SoundOptions,
+    crossfade: bool,
+    emitter: Option<Entity>,
+) -> SoundHandle {
+    let entity = commands.spawn_empty().id();
+    let tag = tag.to_string();
+    commands.add(move |world: &mut World| {
+        start_sound(world, entity, tag, source, options, crossfade, emitter);
+    });
+    SoundHandle(entity)
+}
+
+/// Starts a sound on a reserved entity, or despawns the entity if the
+/// sound mustn't play. With `crossfade`, other sounds on the channel fade
+/// out while this one fades in, unless it is already playing.
+fn start_sound(
+    world: &mut World,
+    entity: Entity,
+    tag: String,
+    source: Handle<AudioSource>,
+    options: SoundOptions,
+    crossfade: bool,
+    emitter: Option<Entity>,
+) {
+    let mixer = world
+        .get_resource::<AudioMixer>()
+        .cloned()
+        .unwrap_or_default();
+    let mut playing = world.query::<&mut PlayingSound>();
+    let mut fade = None;
+    if crossfade {
+        let mut already_playing = false;
+        for mut sound in playing.iter_mut(world) {
+            if sound.channel != options.channel {
+                continue;
+            }
+            if sound.tag == tag && !sound.is_stopping() {
+                already_playing = true;
+            } else {
+                sound.fade_out(mixer.crossfade);
+            }
+        }
+        if already_playing {
+            world.despawn(entity);
+            return;
+        }
+        fade = Some(Fade {
+            from: 0.0,
+            to: 1.0,
+            seconds: mixer.crossfade,
+            elapsed: 0.0,
+            stop: false,
+        });
+    } else if let Some(max) = mixer.max_instances.get(&tag) {
+        let instances = playing
+            .iter(world)
+            .filter(|sound| sound.tag == tag && !sound.is_stopping())
+            .count();
+        if instances >= *max {
+            world.despawn(entity);
+            return;
+        }
+    }
+
+    let sound = PlayingSound {
+        tag,
+        channel: options.channel,
+        volume: options.volume,
+        gain: if fade.is_some() && mixer.crossfade > 0.0 {
+            0.0
+        } else {
+            1.0
+        },
+        fade,
+        emitter,
+    };
+    let settings = PlaybackSettings {
+        mode: if options.looped {
+            PlaybackMode::Loop
+        } else {
+            PlaybackMode::Despawn
+        },
+        volume: Volume::new_relative(sound.mixed_volume(&mixer)),
+        speed: options.speed,
+        spatial: options.position.is_some(),
+        ..default()
+    };
+    let Some(mut entity) = world.get_entity_mut(entity) else {
+        return;
+    };
+    entity.insert((AudioBundle { source, settings }, sound));
+    if let Some(position) = options.position {
+        entity.insert(TransformBundle::from_transform(
+            Transform::from_translation(position.extend(0.0)),
+        ));
+    }
+}
+
+/// Moves fades along, stops sounds that have faded out or whose emitter
+/// has gone, moves emitted sounds with their emitters and applies channel
+/// volumes to playing sounds.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_sounds(
+    time: Res<Time>,
+    mixer: Res<AudioMixer>,
+    global_volume: Option<Res<GlobalVolume>>,
+    mut sounds: Query<(
+        Entity,
+        &mut PlayingSound,
+        Option<&mut Transform>,
+        Option<&AudioSink>,
+        Option<&SpatialAudioSink>,
+    )>,
+    emitters: Query<&PhysicsPosition, With<SoundEmitter>>,
+    mut commands: Commands,
+) {
+    let global_volume = global_volume.map_or(1.0, |global| global.volume.get());
+    for (entity, mut sound, transform, sink, spatial_sink) in sounds.iter_mut() {
+        if !sound.advance(time.delta_seconds()) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(emitter) = sound.emitter {
+            let Ok(position) = emitters.get(emitter) else {
+                commands.entity(entity).despawn();
+                continue;
+            };
+            if let Some(mut transform) = transform {
+                transform.translation = position.end_frame.extend(0.0);
+            }
+        }
+        let volume = global_volume * sound.mixed_volume(&mixer);
+        if let Some(sink) = sink {
+            sink.set_volume(volume);
+        }
+        if let Some(sink) = spatial_sink {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+/// Starts the sound of each new [`SoundEmitter`], once the sound has
+/// loaded.
+pub(crate) fn start_emitters(
+    store: Option<Res<AssetStore>>,
+    loaded_assets: Option<Res<LoadedAssets>>,
+    mut emitters: Query<(Entity, &mut SoundEmitter, &PhysicsPosition)>,
+    mut commands: Commands,
+) {
+    let (Some(store), Some(loaded_assets)) = (store, loaded_assets) else {
+        return;
+    };
+    for (entity, mut emitter, position) in emitters.iter_mut() {
+        if emitter.sound.is_some() {
+            continue;
+        }
+        let Ok(source) = store.try_get_sound(&emitter.tag, &loaded_assets) else {
+            continue;
+        };
+        emitter.sound = Some(spawn_sound(
+            &mut commands,
+            &emitter.tag,
+            source,
+            emitter.options.at(position.end_frame),
+            false,
+            Some(entity),
+        ));
+    }
+}
+
+/// Keeps a [`SpatialListener`] on the active camera that renders last, so
+/// that sounds with a position are heard from where the player is looking.
+/// See [`AudioMixer::with_hearing_distance`] for how far it hears.
+pub(crate) fn update_listener(
+    mixer: Res<AudioMixer>,
+    spatial_scale: Option<ResMut<SpatialScale>>,
+    cameras: Query<(Entity, &Camera, Option<&SpatialListener>)>,
+    mut commands: Commands,
+) {
+    if let Some(mut spatial_scale) = spatial_scale {
+        if mixer.is_changed() {
+            *spatial_scale = SpatialScale::new_2d(4.0 / mixer.hearing_distance);
+        }
+    }
+    let listening = cameras
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+        .max_by_key(|(_, camera, _)| camera.order)
+        .map(|(entity, _, _)| entity);
+    let gap = mixer.hearing_distance / 2.0;
+    for (entity, _, listener) in cameras.iter() {
+        if Some(entity) != listening {
+            if listener.is_some() {
+                commands.entity(entity).remove::<SpatialListener>();
+            }
+        } else if !listener
+            .is_some_and(|listener| (listener.right_ear_offset.x * 2.0 - gap).abs() <= f32::EPSILON)
+        {
+            commands.entity(entity).insert(SpatialListener::new(gap));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn play(world: &mut World, tag: &str, options: SoundOptions, crossfade: bool) -> Entity {
+        let entity = world.spawn_empty().id();
+        start_sound(
+            world,
+            entity,
+            tag.to_string(),
+            Handle::default(),
+            options,
+            crossfade,
+            None,
+        );
+        entity
+    }
+
+    fn playing(world: &mut World) -> Vec<PlayingSound> {
+        let mut sounds = world.query::<&PlayingSound>();
+        sounds.iter(world).cloned().collect()
+    }
+
+    #[test]
+    fn test_max_instances() {
+        let mut world = World::new();
+        world.insert_resource(
+            AudioMixer::default()
+                .with_volume(AudioChannel::Sfx, 0.5)
+                .with_max_instances("flap", 2),
+        );
+        let first = play(&mut world, "flap", SoundOptions::default(), false);
+        play(&mut world, "flap", SoundOptions::default(), false);
+        let third = play(&mut world, "flap", SoundOptions::default(), false);
+        play(&mut world, "crash", SoundOptions::default(), false);
+        assert_eq!(playing(&mut world).len(), 3);
+        assert!(world.get_entity(third).is_none());
+
+        let settings = world.get::<PlaybackSettings>(first).unwrap();
+        assert!(matches!(settings.mode, PlaybackMode::Despawn));
+        assert!(matches!(settings.volume, Volume::Relative(level) if level.get() == 0.5));
+
+        // A copy that is fading out makes room for another.
+        world.get_mut::<PlayingSound>(first).unwrap().fade_out(1.0);
+        play(&mut world, "flap", SoundOptions::default(), false);
+        assert_eq!(playing(&mut world).len(), 4);
+    }
+
+    #[test]
+    fn test_fades() {
+        let mut sound = PlayingSound {
+            tag: "theme".to_string(),
+            channel: AudioChannel::Music,
+            volume: 0.8,
+            gain: 1.0,
+            fade: None,
+            emitter: None,
+        };
+        sound.fade_out(2.0);
+        assert!(sound.advance(1.0));
+        assert_eq!(sound.gain, 0.5);
+        let mixer = AudioMixer::default().with_volume(AudioChannel::Music, 0.5);
+        assert_eq!(sound.mixed_volume(&mixer), 0.2);
+        assert!(!sound.advance(1.5));
+        assert_eq!(sound.gain, 0.0);
+
+        sound.fade_out(0.0);
+        assert!(!sound.advance(0.0));
+    }
+
+    #[test]
+    fn test_crossfade() {
+        let mut world = World::new();
+        let music = SoundOptions::on(AudioChannel::Music).looped();
+        let theme = play(&mut world, "theme", music, true);
+        let ui = play(
+            &mut world,
+            "click",
+            SoundOptions::on(AudioChannel::Ui),
+            false,
+        );
+        let boss = play(&mut world, "boss", music, true);
+        assert!(world.get::<PlayingSound>(theme).unwrap().is_stopping());
+        assert!(!world.get::<PlayingSound>(ui).unwrap().is_stopping());
+        let boss_sound = world.get::<PlayingSound>(boss).unwrap();
+        assert_eq!(boss_sound.gain, 0.0);
+        assert!(!boss_sound.is_stopping());
+        assert!(matches!(
+            world.get::<PlaybackSettings>(boss).unwrap().mode,
+            PlaybackMode::Loop
+        ));
+
+        // The boss theme is already playing, so it carries on.
+        let again = play(&mut world, "boss", music, true);
+        assert!(world.get_entity(again).is_none());
+        assert_eq!(playing(&mut world).len(), 3);
+    }
+
+    #[test]
+    fn test_spatial_sounds() {
+        let mut world = World::new();
+        let crash = play(
+            &mut world,
+            "crash",
+            SoundOptions::default().at(Vec2::new(100.0, 50.0)),
+            false,
+        );
+        assert!(world.get::<PlaybackSettings>(crash).unwrap().spatial);
+        assert_eq!(
+            world.get::<Transform>(crash).unwrap().translation,
+            Vec3::new(100.0, 50.0, 0.0)
+        );
+        let flap = play(&mut world, "flap", SoundOptions::default(), false);
+        assert!(!world.get::<PlaybackSettings>(flap).unwrap().spatial);
+        assert!(world.get::<Transform>(flap).is_none());
+    }
+
+    #[test]
+    fn test_emitters() {
+        use bevy::ecs::system::RunSystemOnce;
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<AudioMixer>();
+        let emitter = world
+            .spawn((
+                PhysicsPosition::new(Vec2::ZERO),
+                SoundEmitter::new("engine"),
+            ))
+            .id();
+        let engine = world.spawn_empty().id();
+        let options = SoundOptions::default().looped().at(Vec2::ZERO);
+        start_sound(
+            &mut world,
+            engine,
+            "engine".to_string(),
+            Handle::default(),
+            options,
+            false,
+            Some(emitter),
+        );
+
+        world.get_mut::<PhysicsPosition>(emitter).unwrap().end_frame = Vec2::new(10.0, 20.0);
+        world.run_system_once(update_sounds);
+        assert_eq!(
+            world.get::<Transform>(engine).unwrap().translation,
+            Vec3::new(10.0, 20.0, 0.0)
+        );
+
+        world.despawn(emitter);
+        world.run_system_once(update_sounds);
+        assert!(world.get_entity(engine).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::{
+    asset::{io::Reader, Asset, AssetApp, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+use std::{any::TypeId, marker::PhantomData};
+
+/// Registers the loader for a data type added with
+/// [`AssetManager::add_data`](crate::AssetManager::add_data).
+///
+/// Bevy picks loaders by file extension, so each registration claims the
+/// full extension of the file it was created for (`wave.ron` for
+/// `first.wave.ron`), and each data type needs its own.
+#[derive(Clone)]
+pub(crate) struct DataRegistration {
+    pub(crate) type_id: TypeId,
+    pub(crate) type_name: &'static str,
+    pub(crate) extension: String,
+    register: fn(&mut App, &'static str),
+}
+
+impl DataRegistration {
+    pub(crate) fn new<T>(filename: &str) -> Self
+    where
+        T: Asset + DeserializeOwned,
+    {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            extension: full_extension(filename),
+            register: register_data_loader::<T>,
+        }
+    }
+
+    pub(crate) fn register(&self, app: &mut App) {
+        // Loaders live for the rest of the program, and Bevy wants their
+        // extensions as `'static` strings.
+        let extension: &'static str = Box::leak(self.extension.clone().into_boxed_str());
+        (self.register)(app, extension);
+    }
+}
+
+fn register_data_loader<T>(app: &mut App, extension: &'static str)
+where
+    T: Asset + DeserializeOwned,
+{
+    if !app.world.contains_resource::<Assets<T>>() {
+        app.init_asset::<T>();
+    }
+    app.register_asset_loader(DataLoader::<T> {
+        extensions: [extension],
+        marker: PhantomData,
+    });
+}
+
+/// Everything after the first dot in the file name, so that `level.ron`
+/// and `first.wave.ron` can be told apart.
+fn full_extension(filename: &str) -> String {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    name.split_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Deserializes JSON files (any extension ending in `json`) or RON files
+/// (everything else) into `T`.
+fn parse_data<T: DeserializeOwned>(extension: &str, bytes: &[u8]) -> anyhow::Result<T> {
+    if extension.ends_with("json") {
+        Ok(serde_json::from_slice(bytes)?)
+    } else {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+}
+
+struct DataLoader<T> {
+    extensions: [&'static str; 1],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> AssetLoader for DataLoader<T>
+where
+    T: Asset + DeserializeOwned,
+{
+    type Asset = T;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<T, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            parse_data(self.extensions[0], &bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Level {
+        name: String,
+        walls: Vec<i32>,
+    }
+
+    #[test]
+    fn test_full_extension() {
+        assert_eq!(full_extension("level.ron"), "ron");
+        assert_eq!(full_extension("levels/first.wave.RON"), "wave.ron");
+        assert_eq!(full_extension("level"), "");
+    }
+
+    #[test]
+    fn test_parse_data() {
+        let expected = Level {
+            name: "First".to_string(),
+            walls: vec![1, -2],
+        };
+        let ron: Level = parse_data("ron", b"(name: \"First\", walls: [1, -2])").unwrap();
+        assert_eq!(ron, expected);
+        let json: Level =
+            parse_data("level.json", br#"{"name": "First", "walls": [1, -2]}"#).unwrap();
+        assert_eq!(json, expected);
+        assert!(parse_data::<Level>("ron", b"(name: 3)").is_err());
+    }
+}
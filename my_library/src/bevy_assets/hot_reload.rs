@@ -0,0 +1,56 @@
+//! Keeps sprite sheets in step with their images when Bevy reloads them.
+//! Build with the `hot_reload` feature to have Bevy watch the `assets`
+//! directory; artists can then edit a sprite sheet while the game runs.
+//! Packed atlases are copies of their images, so they are packed again.
+use crate::{AssetStore, LoadedAssets};
+use bevy::prelude::*;
+
+/// Rebuilds the texture atlas of every sprite sheet whose image was just
+/// reloaded. The atlas keeps its handle, so sprites and their
+/// [`AnimationCycle`](crate::AnimationCycle)s carry on with the new frames.
+/// The grid is sized from the new image, so frames added to the end of a
+/// sheet become available too.
+pub(crate) fn rebuild_atlases(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    store: Option<ResMut<AssetStore>>,
+    loaded_assets: Option<Res<LoadedAssets>>,
+    images: Option<ResMut<Assets<Image>>>,
+    texture_atlases: Option<ResMut<Assets<TextureAtlas>>>,
+) {
+    let (Some(mut store), Some(loaded_assets), Some(mut images), Some(mut texture_atlases)) =
+        (store, loaded_assets, images, texture_atlases)
+    else {
+        image_events.clear();
+        return;
+    };
+    for event in image_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(image_size) = images.get(*id).map(Image::size_f32) else {
+            continue;
+        };
+        for (tag, source) in store.atlas_sources.iter() {
+            let Some(texture) = store.get_image(&source.texture_tag, &loaded_assets) else {
+                continue;
+            };
+            let Some(atlas_handle) = store.atlases.get(tag) else {
+                continue;
+            };
+            if texture.id() != *id {
+                continue;
+            }
+            info!("Reloaded sprite sheet {tag}");
+            texture_atlases.insert(
+                atlas_handle.id(),
+                source.layout.build(texture, Some(image_size)),
+            );
+        }
+        store.build_packs(
+            &loaded_assets,
+            &mut images,
+            &mut texture_atlases,
+            |to_pack| to_pack.iter().any(|(_, handle)| handle.id() == *id),
+        );
+    }
+}
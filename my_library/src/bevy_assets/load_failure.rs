@@ -0,0 +1,73 @@
+use std::fmt;
+
+/// What the loading screen does when an asset fails to load. Set it with
+/// [`GameStatePlugin::with_load_failure`](crate::GameStatePlugin::with_load_failure).
+///
+/// Every failure is logged and kept in
+/// [`AssetStore::failures`](crate::AssetStore::failures), whichever you
+/// choose.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum LoadFailure<T> {
+    /// Load the tag's fallback file, if it has one (see
+    /// [`AssetManager::with_fallback`](crate::AssetManager::with_fallback)),
+    /// or use Bevy's default handle for it, and carry on to the menu.
+    #[default]
+    Fallback,
+    /// Try loading the file again, up to this many times, then fall back.
+    Retry(u32),
+    /// Switch to this state---typically an error screen that lists
+    /// [`AssetStore::failures`](crate::AssetStore::failures).
+    ErrorState(T),
+}
+
+impl<T> LoadFailure<T> {
+    pub(crate) fn retries(&self) -> u32 {
+        match self {
+            LoadFailure::Retry(retries) => *retries,
+            _ => 0,
+        }
+    }
+}
+
+/// An asset the loading screen couldn't load.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetLoadFailure {
+    pub tag: String,
+    pub filename: String,
+    pub error: String,
+}
+
+impl fmt::Display for AssetLoadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unable to load {} from {}: {}",
+            self.tag, self.filename, self.error
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retries() {
+        assert_eq!(LoadFailure::<u8>::Fallback.retries(), 0);
+        assert_eq!(LoadFailure::<u8>::Retry(3).retries(), 3);
+        assert_eq!(LoadFailure::ErrorState(1_u8).retries(), 0);
+    }
+
+    #[test]
+    fn test_failure_report() {
+        let failure = AssetLoadFailure {
+            tag: "wall".to_string(),
+            filename: "wall.png".to_string(),
+            error: "bad PNG".to_string(),
+        };
+        assert_eq!(
+            failure.to_string(),
+            "unable to load wall from wall.png: bad PNG"
+        );
+    }
+}
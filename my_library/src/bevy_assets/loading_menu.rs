@@ -1,16 +1,220 @@
-use crate::{egui::egui::Window, AssetStore, MenuResource};
-use bevy::{asset::LoadedUntypedAsset, prelude::*};
+use crate::{
+    egui::egui::{ProgressBar, Window},
+    AssetLoadFailure, AssetStore, LoadFailure, MenuResource,
+};
+use bevy::{
+    asset::{LoadState, LoadedUntypedAsset},
+    prelude::*,
+    tasks::IoTaskPool,
+};
 use bevy_egui::EguiContexts;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// How long to wait before loading a failed asset again. Bevy only forgets
+/// a failed load once every handle to it has been dropped.
+const RETRY_DELAY: f32 = 0.5;
+
+enum LoadStatus {
+    Loading(Handle<LoadedUntypedAsset>),
+    /// The load failed, and we're waiting to hear why.
+    Diagnosing,
+    RetryAt(f32),
+    Loaded,
+    /// The load failed and the tag was given a placeholder.
+    Failed,
+}
+
+impl LoadStatus {
+    fn is_finished(&self) -> bool {
+        matches!(self, LoadStatus::Loaded | LoadStatus::Failed)
+    }
+}
+
+struct PendingAsset {
+    tag: String,
+    filename: String,
+    status: LoadStatus,
+    retries: u32,
+    /// The file size in bytes, which weights the progress bar.
+    size: u64,
+}
 
 #[derive(Resource)]
-pub(crate) struct AssetsToLoad(Vec<Handle<LoadedUntypedAsset>>); //(1)
+pub(crate) struct AssetsToLoad {
+    assets: Vec<PendingAsset>, //(1)
+    /// Why each failed load failed, as (tag, error), filled in by
+    /// background tasks.
+    errors: Arc<Mutex<Vec<(String, String)>>>,
+    started: f32,
+    splash_shown: bool,
+}
 
-pub(crate) fn setup(assets: Res<AssetStore>, mut commands: Commands) {
-    let assets_to_load: Vec<Handle<LoadedUntypedAsset>> =
-        assets.asset_index.values().cloned().collect(); //(2)
-    commands.insert_resource(AssetsToLoad(assets_to_load)); //(3)
+impl AssetsToLoad {
+    /// Handles the load errors diagnosed since the last frame. Each failed
+    /// asset is retried if `load_failure` allows, or else recorded in
+    /// [`AssetStore::failures`] and given its fallback or a placeholder.
+    /// Returns the state to switch to if `load_failure` calls for an error
+    /// state, once every failure has been recorded.
+    fn record_errors<T: Clone>(
+        &mut self,
+        asset_server: &AssetServer,
+        store: &mut AssetStore,
+        load_failure: &LoadFailure<T>,
+        now: f32,
+    ) -> Option<T> {
+        let diagnosed =
+            std::mem::take(&mut *self.errors.lock().unwrap_or_else(PoisonError::into_inner));
+        let mut error_state = None;
+        for (tag, error) in diagnosed {
+            let Some(pending) = self.assets.iter_mut().find(|p| p.tag == tag) else {
+                continue;
+            };
+            if pending.retries < load_failure.retries() {
+                pending.retries += 1;
+                warn!(
+                    "Unable to load {tag} from {}, retrying: {error}",
+                    pending.filename
+                );
+                pending.status = LoadStatus::RetryAt(now + RETRY_DELAY);
+                continue;
+            }
+
+            let failure = AssetLoadFailure {
+                tag: tag.clone(),
+                filename: pending.filename.clone(),
+                error,
+            };
+            error!("{failure}");
+            store.failures.push(failure);
+            if let LoadFailure::ErrorState(state) = load_failure {
+                pending.status = LoadStatus::Failed;
+                error_state = Some(state.clone());
+            } else if let Some(fallback) = store.fallbacks.remove(&tag) {
+                pending.filename = fallback;
+                pending.status = load(asset_server, store, &tag, &pending.filename);
+            } else {
+                store.placeholders.insert(tag);
+                pending.status = LoadStatus::Failed;
+            }
+        }
+        error_state
+    }
+
+    /// How far through loading we are, from 0 to 1, by file size.
+    fn progress(&self) -> f32 {
+        let total: u64 = self.assets.iter().map(|asset| asset.size).sum();
+        if total == 0 {
+            return 1.0;
+        }
+        let finished: u64 = self
+            .assets
+            .iter()
+            .filter(|asset| asset.status.is_finished())
+            .map(|asset| asset.size)
+            .sum();
+        finished as f32 / total as f32
+    }
+
+    fn is_finished(&self) -> bool {
+        self.assets.iter().all(|asset| asset.status.is_finished())
+    }
+
+    fn tags(&self, filter: impl Fn(&LoadStatus) -> bool) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .assets
+            .iter()
+            .filter(|asset| filter(&asset.status))
+            .map(|asset| asset.tag.as_str())
+            .collect();
+        tags.sort();
+        tags
+    }
 }
 
+/// Marks the splash image and its camera, so they can be removed when
+/// loading finishes.
+#[derive(Component)]
+pub(crate) struct LoadingElement;
+
+/// Where the loading screen goes once it's done, if not the main menu.
+#[derive(Resource)]
+pub(crate) struct LoadingTarget<T>(pub(crate) Option<T>);
+
+/// Runs before each state transition. Unloads the asset groups the next
+/// state doesn't use, and detours through the loading screen if it needs
+/// groups that aren't loaded yet.
+pub(crate) fn enter_asset_groups<T>(
+    mut next_state: ResMut<NextState<T>>,
+    mut target: ResMut<LoadingTarget<T>>,
+    menu_info: Res<MenuResource<T>>,
+    store: Option<ResMut<AssetStore>>,
+) where
+    T: States,
+{
+    let (Some(next), Some(mut store)) = (next_state.0.clone(), store) else {
+        return;
+    };
+    if next == T::default() {
+        return;
+    }
+    let groups = menu_info.groups_for(&next);
+    let unused: Vec<String> = store
+        .loaded_groups
+        .iter()
+        .filter(|group| !groups.contains(&group.as_str()))
+        .cloned()
+        .collect();
+    for group in unused {
+        store.unload_group(&group);
+    }
+    if groups.iter().any(|group| !store.is_group_loaded(group)) {
+        next_state.set(T::default());
+        target.0 = Some(next);
+    }
+}
+
+pub(crate) fn setup<T>(
+    mut assets: ResMut<AssetStore>,
+    asset_server: Res<AssetServer>,
+    menu_info: Res<MenuResource<T>>,
+    target: Res<LoadingTarget<T>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) where
+    T: States,
+{
+    let target = target.0.as_ref().unwrap_or(&menu_info.menu_state);
+    for group in menu_info.groups_for(target) {
+        if !assets.is_group_loaded(group) && !assets.load_group(&asset_server, group) {
+            warn!("No assets are registered in group {group}");
+        }
+    }
+
+    // Only wait for assets that haven't already loaded on an earlier visit.
+    let to_load = assets
+        .asset_index
+        .iter()
+        .filter(|(_, handle)| asset_server.get_load_state(handle.id()) != Some(LoadState::Loaded))
+        .map(|(tag, handle)| {
+            let filename = assets.files.get(tag).cloned().unwrap_or_default();
+            PendingAsset {
+                tag: tag.clone(),
+                size: assets.file_size(&filename),
+                filename,
+                status: LoadStatus::Loading(handle.clone()),
+                retries: 0,
+            }
+        })
+        .collect(); //(2)
+    commands.insert_resource(AssetsToLoad {
+        assets: to_load,
+        errors: Arc::default(),
+        started: time.elapsed_seconds(),
+        splash_shown: false,
+    }); //(3)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn run<T>(
     asset_server: Res<AssetServer>,
     mut to_load: ResMut<AssetsToLoad>,
@@ -20,31 +224,144 @@ pub(crate) fn run<T>(
     mut store: ResMut<AssetStore>,
     loaded_assets: Res<crate::LoadedAssets>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    time: Res<Time>,
+    mut commands: Commands,
+    target: Res<LoadingTarget<T>>,
 ) where
     T: States,
 {
-    to_load.0.retain(|handle| {
+    let now = time.elapsed_seconds();
+    let to_load = &mut *to_load;
+    if let Some(error_state) =
+        to_load.record_errors(&asset_server, &mut store, &menu_info.load_failure, now)
+    {
+        state.set(error_state);
+        return;
+    }
+
+    for pending in to_load.assets.iter_mut() {
         //(4)
-        match asset_server.get_load_state(handle.id()) {
-            //(5)
-            Some(bevy::asset::LoadState::Loaded) => false, //(6)
-            _ => true,                                     //(7)
+        match &pending.status {
+            LoadStatus::Loading(handle) => match asset_server.get_load_state(handle.id()) {
+                //(5)
+                Some(LoadState::Loaded) => pending.status = LoadStatus::Loaded, //(6)
+                Some(LoadState::Failed) => {
+                    // Drop every handle to the failed load, so that a retry
+                    // starts from scratch.
+                    store.asset_index.remove(&pending.tag);
+                    diagnose(
+                        &asset_server,
+                        &pending.tag,
+                        &store.load_path(&pending.filename),
+                        &to_load.errors,
+                    );
+                    pending.status = LoadStatus::Diagnosing;
+                }
+                _ => {} //(7)
+            },
+            LoadStatus::RetryAt(retry_at) if *retry_at <= now => {
+                pending.status = load(&asset_server, &mut store, &pending.tag, &pending.filename);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(splash) = &menu_info.loading_screen.splash_image {
+        if !to_load.splash_shown && !store.is_placeholder(splash) {
+            if let Some(texture) = store.get_image(splash, &loaded_assets) {
+                commands.spawn((Camera2dBundle::default(), LoadingElement));
+                commands.spawn((
+                    SpriteBundle {
+                        texture,
+                        ..default()
+                    },
+                    LoadingElement,
+                ));
+                to_load.splash_shown = true;
+            }
         }
-    });
-    if to_load.0.is_empty() {
+    }
+
+    if to_load.is_finished() && now - to_load.started >= menu_info.loading_screen.min_time {
         //(8)
         load_atlases(&mut store, &loaded_assets, &mut texture_atlases);
-        state.set(menu_info.menu_state.clone());
+        store.build_packs(&loaded_assets, &mut images, &mut texture_atlases, |_| false);
+        state.set(
+            target
+                .0
+                .clone()
+                .unwrap_or_else(|| menu_info.menu_state.clone()),
+        );
     }
     Window::new("Loading, Please Wait").show(
         //(9)
         egui_context.ctx_mut(),
-        |ui| ui.label(format!("{} assets remaining", to_load.0.len())),
+        |ui| {
+            ui.add(ProgressBar::new(to_load.progress()).show_percentage());
+            let pending = to_load.tags(|status| !status.is_finished());
+            if !pending.is_empty() {
+                ui.label(format!("Loading: {}", pending.join(", ")));
+            }
+            let loaded = to_load.tags(|status| matches!(status, LoadStatus::Loaded));
+            if !loaded.is_empty() {
+                ui.label(format!("Loaded: {}", loaded.join(", ")));
+            }
+            for failure in store.failures.iter() {
+                ui.colored_label(crate::egui::egui::Color32::RED, failure.to_string());
+            }
+        },
     );
 }
 
-pub(crate) fn exit(mut commands: Commands) {
+pub(crate) fn exit<T>(
+    query: Query<Entity, With<LoadingElement>>,
+    mut target: ResMut<LoadingTarget<T>>,
+    mut commands: Commands,
+) where
+    T: States,
+{
     commands.remove_resource::<AssetsToLoad>();
+    target.0 = None;
+    query.for_each(|entity| commands.entity(entity).despawn());
+}
+
+fn load(
+    asset_server: &AssetServer,
+    store: &mut AssetStore,
+    tag: &str,
+    filename: &str,
+) -> LoadStatus {
+    let handle = asset_server.load_untyped(store.load_path(filename));
+    store.asset_index.insert(tag.to_string(), handle.clone());
+    store.files.insert(tag.to_string(), filename.to_string());
+    LoadStatus::Loading(handle)
+}
+
+/// Bevy logs why a load failed, but doesn't keep the error, so load the
+/// file again in the background to find out.
+fn diagnose(
+    asset_server: &AssetServer,
+    tag: &str,
+    filename: &str,
+    errors: &Arc<Mutex<Vec<(String, String)>>>,
+) {
+    let server = asset_server.clone();
+    let tag = tag.to_string();
+    let filename = filename.to_string();
+    let errors = errors.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let error = match server.load_untyped_async(filename).await {
+                Ok(_) => "the asset loader failed; see the log for details".to_string(),
+                Err(error) => error.to_string(),
+            };
+            errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((tag, error));
+        })
+        .detach();
 }
 
 fn load_atlases(
@@ -52,19 +369,182 @@ fn load_atlases(
     loaded_assets: &crate::LoadedAssets,
     texture_atlases: &mut Assets<TextureAtlas>,
 ) {
-    for new_atlas in store.atlases_to_build.iter() {
-        let texture_handle = store
-            .get_handle(&new_atlas.texture_tag, loaded_assets)
-            .unwrap();
-        let atlas = TextureAtlas::from_grid(
-            texture_handle,
-            new_atlas.tile_size,
-            new_atlas.sprites_x,
-            new_atlas.sprites_y,
-            None,
-            None,
-        );
+    for new_atlas in std::mem::take(&mut store.atlases_to_build) {
+        let Some(texture_handle) = store.get_image(&new_atlas.texture_tag, loaded_assets) else {
+            warn!(
+                "Unable to build sprite sheet {}: its image didn't load",
+                new_atlas.tag
+            );
+            continue;
+        };
+        let atlas = new_atlas.layout.build(texture_handle, None);
         let atlas_handle = texture_atlases.add(atlas);
         store.atlases.insert(new_atlas.tag.clone(), atlas_handle);
+        store.atlas_sources.insert(new_atlas.tag.clone(), new_atlas);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LoadingScreen, MenuAssets};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+    enum TestState {
+        #[default]
+        Loading,
+        Menu,
+        Level1,
+        Level2,
+    }
+
+    fn group_world(next: TestState, loaded_groups: &[&str]) -> World {
+        let mut world = World::new();
+        world.insert_resource(NextState(Some(next)));
+        world.insert_resource(LoadingTarget::<TestState>(None));
+        world.insert_resource(MenuResource {
+            menu_state: TestState::Menu,
+            game_start_state: TestState::Level1,
+            game_end_state: TestState::Menu,
+            menu_assets: MenuAssets {
+                main_menu: String::new(),
+                game_over: String::new(),
+            },
+            load_failure: LoadFailure::Fallback,
+            loading_screen: LoadingScreen {
+                min_time: 0.0,
+                splash_image: None,
+            },
+            asset_groups: vec![
+                (TestState::Level1, "level1".to_string()),
+                (TestState::Level1, "shared".to_string()),
+                (TestState::Level2, "level2".to_string()),
+                (TestState::Level2, "shared".to_string()),
+            ],
+        });
+        let mut store = AssetStore::default();
+        for group in loaded_groups {
+            store.loaded_groups.insert(group.to_string());
+        }
+        world.insert_resource(store);
+        world
+    }
+
+    #[test]
+    fn test_entering_a_state_loads_its_groups() {
+        let mut world = group_world(TestState::Level2, &["level1", "shared"]);
+        world.run_system_once(enter_asset_groups::<TestState>);
+
+        assert_eq!(
+            world.resource::<NextState<TestState>>().0,
+            Some(TestState::Loading)
+        );
+        assert_eq!(
+            world.resource::<LoadingTarget<TestState>>().0,
+            Some(TestState::Level2)
+        );
+        let store = world.resource::<AssetStore>();
+        assert!(!store.is_group_loaded("level1"));
+        assert!(store.is_group_loaded("shared"));
+    }
+
+    #[test]
+    fn test_leaving_a_state_unloads_its_groups() {
+        let mut world = group_world(TestState::Menu, &["level1", "shared"]);
+        world.run_system_once(enter_asset_groups::<TestState>);
+
+        assert_eq!(
+            world.resource::<NextState<TestState>>().0,
+            Some(TestState::Menu)
+        );
+        assert_eq!(world.resource::<LoadingTarget<TestState>>().0, None);
+        assert!(world.resource::<AssetStore>().loaded_groups.is_empty());
+    }
+
+    #[test]
+    fn test_loaded_groups_need_no_loading_screen() {
+        let mut world = group_world(TestState::Level2, &["level2", "shared"]);
+        world.run_system_once(enter_asset_groups::<TestState>);
+        assert_eq!(
+            world.resource::<NextState<TestState>>().0,
+            Some(TestState::Level2)
+        );
+    }
+
+    fn asset(tag: &str, size: u64, status: LoadStatus) -> PendingAsset {
+        PendingAsset {
+            tag: tag.to_string(),
+            filename: format!("{tag}.png"),
+            status,
+            retries: 0,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_progress_is_weighted_by_size() {
+        let mut to_load = AssetsToLoad {
+            assets: vec![
+                asset("wall", 300, LoadStatus::Loaded),
+                asset("dragon", 100, LoadStatus::Failed),
+                asset("music", 600, LoadStatus::Diagnosing),
+            ],
+            errors: Arc::default(),
+            started: 0.0,
+            splash_shown: false,
+        };
+        assert_eq!(to_load.progress(), 0.4);
+        assert!(!to_load.is_finished());
+        assert_eq!(to_load.tags(|status| !status.is_finished()), ["music"]);
+        assert_eq!(
+            to_load.tags(|status| status.is_finished()),
+            ["dragon", "wall"]
+        );
+
+        to_load.assets[2].status = LoadStatus::Loaded;
+        assert_eq!(to_load.progress(), 1.0);
+        assert!(to_load.is_finished());
+    }
+
+    #[test]
+    fn test_error_state_records_every_failure() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()));
+        let asset_server = app.world.resource::<AssetServer>();
+        let mut to_load = AssetsToLoad {
+            assets: vec![
+                asset("wall", 100, LoadStatus::Diagnosing),
+                asset("dragon", 100, LoadStatus::Diagnosing),
+            ],
+            errors: Arc::new(Mutex::new(vec![
+                ("wall".to_string(), "not found".to_string()),
+                ("dragon".to_string(), "corrupt".to_string()),
+            ])),
+            started: 0.0,
+            splash_shown: false,
+        };
+        let mut store = AssetStore::default();
+        let error_state = to_load.record_errors(
+            asset_server,
+            &mut store,
+            &LoadFailure::ErrorState(TestState::Menu),
+            0.0,
+        );
+        assert_eq!(error_state, Some(TestState::Menu));
+        let failed: Vec<&str> = store.failures.iter().map(|f| f.tag.as_str()).collect();
+        assert_eq!(failed, ["wall", "dragon"]);
+    }
+
+    #[test]
+    fn test_nothing_to_load() {
+        let to_load = AssetsToLoad {
+            assets: Vec::new(),
+            errors: Arc::default(),
+            started: 0.0,
+            splash_shown: false,
+        };
+        assert_eq!(to_load.progress(), 1.0);
+        assert!(to_load.is_finished());
     }
 }
@@ -0,0 +1,320 @@
+use bevy::{prelude::*, sprite::TextureAtlasBuilder};
+use serde::Deserialize;
+
+/// How a sprite sheet's image is cut into sprites.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum AtlasLayout {
+    /// Evenly sized sprites in rows and columns.
+    Grid {
+        tile_size: Vec2,
+        sprites_x: usize,
+        sprites_y: usize,
+        padding: Option<Vec2>,
+        offset: Option<Vec2>,
+    },
+    /// Sprites of any size, wherever a packing tool put them.
+    Packed(PackedSheet),
+}
+
+impl AtlasLayout {
+    /// Builds the atlas. If the image's size is given, grids are resized to
+    /// fit it, so that a sheet which has grown gains the extra sprites.
+    pub(crate) fn build(&self, texture: Handle<Image>, image_size: Option<Vec2>) -> TextureAtlas {
+        match self {
+            AtlasLayout::Grid {
+                tile_size,
+                sprites_x,
+                sprites_y,
+                padding,
+                offset,
+            } => {
+                let (sprites_x, sprites_y) = match image_size {
+                    Some(image_size) => grid_size(image_size, *tile_size, *padding, *offset),
+                    None => (*sprites_x, *sprites_y),
+                };
+                TextureAtlas::from_grid(
+                    texture, *tile_size, sprites_x, sprites_y, *padding, *offset,
+                )
+            }
+            AtlasLayout::Packed(sheet) => {
+                let mut atlas = TextureAtlas::new_empty(texture, sheet.size);
+                for (_, frame) in sheet.frames.iter() {
+                    atlas.add_texture(*frame);
+                }
+                atlas
+            }
+        }
+    }
+
+    /// The index of a named frame, for packed sheets.
+    pub(crate) fn frame_index(&self, name: &str) -> Option<usize> {
+        match self {
+            AtlasLayout::Grid { .. } => None,
+            AtlasLayout::Packed(sheet) => sheet
+                .frames
+                .iter()
+                .position(|(frame_name, _)| frame_name == name),
+        }
+    }
+}
+
+/// How many whole tiles fit across and down an image, and at least one.
+fn grid_size(
+    image_size: Vec2,
+    tile_size: Vec2,
+    padding: Option<Vec2>,
+    offset: Option<Vec2>,
+) -> (usize, usize) {
+    let padding = padding.unwrap_or_default();
+    let usable = image_size - offset.unwrap_or_default() + padding;
+    let tiles = (usable / (tile_size + padding)).floor().max(Vec2::ONE);
+    (tiles.x as usize, tiles.y as usize)
+}
+
+/// Separate images that are copied into one texture when they load, so
+/// that sprites drawn from any of them share a texture.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct ImagePack {
+    /// The tag of the atlas the images are packed into.
+    pub(crate) tag: String,
+    /// The tags of the images.
+    pub(crate) images: Vec<String>,
+}
+
+/// Copies images into a new texture. Returns the atlas, and the index of
+/// each image in it, in the order the images were given.
+pub(crate) fn pack_images(
+    to_pack: &[(String, Handle<Image>)],
+    images: &mut Assets<Image>,
+) -> anyhow::Result<(TextureAtlas, Vec<usize>)> {
+    let mut builder = TextureAtlasBuilder::default();
+    for (tag, handle) in to_pack.iter() {
+        let image = images
+            .get(handle)
+            .ok_or_else(|| anyhow::Error::msg(format!("the image {tag} isn't loaded")))?;
+        builder.add_texture(handle.id(), image);
+    }
+    let atlas = builder.finish(images)?;
+    let indices = to_pack
+        .iter()
+        .map(|(tag, handle)| {
+            atlas.get_texture_index(handle).ok_or_else(|| {
+                anyhow::Error::msg(format!("the image {tag} is missing from the atlas"))
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((atlas, indices))
+}
+
+/// A sprite sheet described by a packing tool's JSON export. TexturePacker
+/// ("JSON (Hash)" or "JSON (Array)") and Aseprite (hash or array) share a
+/// layout, so both are read the same way.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PackedSheet {
+    /// The image, relative to the `assets` directory.
+    pub(crate) image: String,
+    pub(crate) size: Vec2,
+    /// Each frame's name and where it sits in the image, in export order.
+    pub(crate) frames: Vec<(String, Rect)>,
+}
+
+#[derive(Deserialize)]
+struct PackerExport {
+    frames: serde_json::Value,
+    meta: PackerMeta,
+}
+
+#[derive(Deserialize)]
+struct PackerMeta {
+    image: String,
+    size: PackerSize,
+}
+
+#[derive(Deserialize)]
+struct PackerSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+struct PackerFrame {
+    #[serde(default)]
+    filename: String,
+    frame: PackerRect,
+    #[serde(default)]
+    rotated: bool,
+}
+
+#[derive(Deserialize)]
+struct PackerRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl PackedSheet {
+    /// Parses a packer's JSON export. `filename` is the JSON file's path in
+    /// the `assets` directory; the image it names is found next to it.
+    pub(crate) fn parse(filename: &str, json: &str) -> anyhow::Result<Self> {
+        let export: PackerExport = serde_json::from_str(json)?;
+        let frames: Vec<PackerFrame> = match export.frames {
+            // Hash exports key each frame by name, in export order.
+            serde_json::Value::Object(frames) => frames
+                .into_iter()
+                .map(|(name, frame)| {
+                    let mut frame: PackerFrame = serde_json::from_value(frame)?;
+                    frame.filename = name;
+                    Ok(frame)
+                })
+                .collect::<anyhow::Result<_>>()?,
+            frames => serde_json::from_value(frames)?,
+        };
+
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                if frame.rotated {
+                    return Err(anyhow::Error::msg(format!(
+                        "frame {} is rotated; export the sheet without rotation",
+                        frame.filename
+                    )));
+                }
+                let PackerRect { x, y, w, h } = frame.frame;
+                Ok((frame.filename, Rect::new(x, y, x + w, y + h)))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let image = match filename.rsplit_once('/') {
+            Some((directory, _)) => format!("{directory}/{}", export.meta.image),
+            None => export.meta.image,
+        };
+        Ok(Self {
+            image,
+            size: Vec2::new(export.meta.size.w, export.meta.size.h),
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEXTURE_PACKER_HASH: &str = r#"{
+        "frames": {
+            "flap_2.png": { "frame": {"x":62,"y":0,"w":62,"h":65}, "rotated": false, "trimmed": false },
+            "flap_1.png": { "frame": {"x":0,"y":0,"w":62,"h":65}, "rotated": false, "trimmed": false },
+            "wall.png": { "frame": {"x":124,"y":0,"w":32,"h":32}, "rotated": false, "trimmed": false }
+        },
+        "meta": { "app": "https://www.codeandweb.com/texturepacker", "image": "flappy.png", "size": {"w":156,"h":65} }
+    }"#;
+
+    const ASEPRITE_ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "flappy 0.aseprite", "frame": { "x": 0, "y": 0, "w": 62, "h": 65 }, "duration": 100 },
+            { "filename": "flappy 1.aseprite", "frame": { "x": 62, "y": 0, "w": 62, "h": 65 }, "duration": 100 }
+        ],
+        "meta": { "app": "https://www.aseprite.org/", "image": "flappy.png", "size": { "w": 124, "h": 65 }, "frameTags": [] }
+    }"#;
+
+    #[test]
+    fn test_parse_texture_packer_hash() {
+        let sheet = PackedSheet::parse("sheets/flappy.json", TEXTURE_PACKER_HASH).unwrap();
+        assert_eq!(sheet.image, "sheets/flappy.png");
+        assert_eq!(sheet.size, Vec2::new(156.0, 65.0));
+        let names: Vec<&str> = sheet.frames.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["flap_2.png", "flap_1.png", "wall.png"]);
+        assert_eq!(sheet.frames[2].1, Rect::new(124.0, 0.0, 156.0, 32.0));
+    }
+
+    #[test]
+    fn test_parse_aseprite_array() {
+        let sheet = PackedSheet::parse("flappy.json", ASEPRITE_ARRAY).unwrap();
+        assert_eq!(sheet.image, "flappy.png");
+        assert_eq!(sheet.frames.len(), 2);
+        assert_eq!(sheet.frames[1].0, "flappy 1.aseprite");
+        assert_eq!(sheet.frames[1].1, Rect::new(62.0, 0.0, 124.0, 65.0));
+
+        let layout = AtlasLayout::Packed(sheet);
+        assert_eq!(layout.frame_index("flappy 1.aseprite"), Some(1));
+        assert_eq!(layout.frame_index("flappy 2.aseprite"), None);
+        let atlas = layout.build(Handle::default(), None);
+        assert_eq!(atlas.len(), 2);
+        assert_eq!(atlas.size, Vec2::new(124.0, 65.0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let rotated = TEXTURE_PACKER_HASH.replacen("\"rotated\": false", "\"rotated\": true", 1);
+        let error = PackedSheet::parse("flappy.json", &rotated).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "frame flap_2.png is rotated; export the sheet without rotation"
+        );
+        assert!(PackedSheet::parse("flappy.json", "{\"frames\": []}").is_err());
+    }
+
+    #[test]
+    fn test_grid_size() {
+        let tile = Vec2::new(62.0, 65.0);
+        assert_eq!(grid_size(Vec2::new(248.0, 65.0), tile, None, None), (4, 1));
+        assert_eq!(grid_size(Vec2::new(310.0, 130.0), tile, None, None), (5, 2));
+        assert_eq!(grid_size(Vec2::new(250.0, 70.0), tile, None, None), (4, 1));
+        assert_eq!(grid_size(Vec2::new(10.0, 10.0), tile, None, None), (1, 1));
+        // Four tiles with 2px between them, after a 4px border.
+        let padding = Some(Vec2::new(2.0, 2.0));
+        let offset = Some(Vec2::new(4.0, 4.0));
+        assert_eq!(
+            grid_size(Vec2::new(258.0, 69.0), tile, padding, offset),
+            (4, 1)
+        );
+    }
+
+    #[test]
+    fn test_pack_images() {
+        use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+        let mut images = Assets::<Image>::default();
+        let mut add_image = |width, height| {
+            images.add(Image::new_fill(
+                Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                &[255, 255, 255, 255],
+                TextureFormat::Rgba8UnormSrgb,
+            ))
+        };
+        let to_pack = vec![
+            ("wall".to_string(), add_image(32, 32)),
+            ("dragon".to_string(), add_image(65, 45)),
+        ];
+        let (atlas, indices) = pack_images(&to_pack, &mut images).unwrap();
+        assert_eq!(atlas.len(), 2);
+        assert_eq!(atlas.textures[indices[0]].size(), Vec2::new(32.0, 32.0));
+        assert_eq!(atlas.textures[indices[1]].size(), Vec2::new(65.0, 45.0));
+        assert!(images.get(&atlas.texture).is_some());
+
+        let missing = vec![("boss".to_string(), Handle::default())];
+        let error = pack_images(&missing, &mut images).unwrap_err();
+        assert_eq!(error.to_string(), "the image boss isn't loaded");
+    }
+
+    #[test]
+    fn test_grid_layout() {
+        let layout = AtlasLayout::Grid {
+            tile_size: Vec2::new(62.0, 65.0),
+            sprites_x: 4,
+            sprites_y: 1,
+            padding: Some(Vec2::new(2.0, 0.0)),
+            offset: Some(Vec2::new(1.0, 0.0)),
+        };
+        let atlas = layout.build(Handle::default(), None);
+        assert_eq!(atlas.len(), 4);
+        assert_eq!(atlas.textures[1], Rect::new(65.0, 0.0, 127.0, 65.0));
+        let atlas = layout.build(Handle::default(), Some(Vec2::new(321.0, 65.0)));
+        assert_eq!(atlas.len(), 5);
+    }
+}
@@ -7,24 +7,106 @@ pub use bevy_physics::*;
 mod bevy_collision;
 pub use bevy_collision::*;
 
+/// The asset tag [`GameStatePlugin`] shows on the main menu by default.
+pub const MAIN_MENU_ASSET: &str = "main_menu";
+/// The asset tag [`GameStatePlugin`] shows on the game over screen by default.
+pub const GAME_OVER_ASSET: &str = "game_over";
+
 pub struct GameStatePlugin<T> {
     menu_state: T,
     game_start_state: T,
     game_end_state: T,
+    menu_assets: MenuAssets,
+    load_failure: crate::LoadFailure<T>,
+    loading_screen: LoadingScreen,
+    asset_groups: Vec<(T, String)>,
+    state_music: Vec<(T, String)>,
 }
 
 impl<T> GameStatePlugin<T>
 where
     T: States,
 {
-    #[allow(clippy::new_without_default)]
     pub fn new(menu_state: T, game_start_state: T, game_end_state: T) -> Self {
         Self {
             menu_state,
             game_start_state,
             game_end_state,
+            menu_assets: MenuAssets {
+                main_menu: MAIN_MENU_ASSET.to_string(),
+                game_over: GAME_OVER_ASSET.to_string(),
+            },
+            load_failure: crate::LoadFailure::Fallback,
+            loading_screen: LoadingScreen {
+                min_time: 0.0,
+                splash_image: None,
+            },
+            asset_groups: Vec::new(),
+            state_music: Vec::new(),
         } //(1)
     }
+
+    /// Sets the image tags shown on the main menu and game over screens.
+    /// If an image isn't registered with the
+    /// [`AssetManager`](crate::AssetManager), a plain text screen is shown
+    /// instead.
+    pub fn with_menu_images<S: ToString>(mut self, main_menu: S, game_over: S) -> Self {
+        self.menu_assets = MenuAssets {
+            main_menu: main_menu.to_string(),
+            game_over: game_over.to_string(),
+        };
+        self
+    }
+
+    /// Sets what the loading screen does when an asset fails to load. The
+    /// default, [`LoadFailure::Fallback`](crate::LoadFailure::Fallback),
+    /// carries on with placeholders.
+    pub fn with_load_failure(mut self, load_failure: crate::LoadFailure<T>) -> Self {
+        self.load_failure = load_failure;
+        self
+    }
+
+    /// Keeps the loading screen up for at least `seconds`, even if every
+    /// asset is already loaded, so a splash image doesn't just flash by.
+    pub fn with_min_loading_time(mut self, seconds: f32) -> Self {
+        self.loading_screen.min_time = seconds;
+        self
+    }
+
+    /// Shows an image from the [`AssetStore`](crate::AssetStore) behind the
+    /// loading screen as soon as it has loaded. Register it with the
+    /// [`AssetManager`](crate::AssetManager) like any other image.
+    pub fn with_splash_image<S: ToString>(mut self, tag: S) -> Self {
+        self.loading_screen.splash_image = Some(tag.to_string());
+        self
+    }
+
+    /// Loads an asset group (see
+    /// [`AssetManager::with_group`](crate::AssetManager::with_group)) when
+    /// `state` is entered, and unloads it when the game moves to a state
+    /// that doesn't use it. The loading screen is shown while the group
+    /// loads. Bind a group to several states to keep it loaded across them.
+    pub fn with_asset_group<S: ToString>(mut self, state: T, group: S) -> Self {
+        self.asset_groups.push((state, group.to_string()));
+        self
+    }
+
+    /// Plays a music track while the game is in `state`. Moving between
+    /// states with different tracks crossfades from one to the next (see
+    /// [`AudioMixer::with_crossfade`](crate::AudioMixer::with_crossfade)).
+    /// States without a track keep whatever is playing.
+    pub fn with_state_music<S: ToString>(mut self, state: T, music: S) -> Self {
+        self.state_music.push((state, music.to_string()));
+        self
+    }
+
+    /// The asset tags the menus display, if they are registered. Pass them
+    /// to [`AssetManager::check_tags`](crate::AssetManager::check_tags) if
+    /// your game ships menu images, so a missing one is an error at startup
+    /// rather than a silent fall back to text.
+    pub fn required_assets(&self) -> [&str; 2] {
+        [&self.menu_assets.main_menu, &self.menu_assets.game_over]
+    }
 }
 
 impl<T> Plugin for GameStatePlugin<T>
@@ -32,16 +114,19 @@ where
     T: States + Copy,
 {
     fn build(&self, app: &mut App) {
-        app.add_event::<PhysicsTick>();
-        app.add_event::<Impulse>();
         app.add_state::<T>(); //(2)
         app.add_plugins(bevy_egui::EguiPlugin);
         let start = MenuResource {
             menu_state: self.menu_state,
             game_start_state: self.game_start_state, //(4)
             game_end_state: self.game_end_state,
+            menu_assets: self.menu_assets.clone(),
+            load_failure: self.load_failure.clone(),
+            loading_screen: self.loading_screen.clone(),
+            asset_groups: self.asset_groups.clone(),
         };
         app.insert_resource(start);
+        app.insert_resource(crate::bevy_assets::LoadingTarget::<T>(None));
 
         app.add_systems(OnEnter(self.menu_state), game_menus::setup::<T>);
         app.add_systems(
@@ -60,20 +145,67 @@ where
             cleanup::<game_menus::MenuElement>,
         );
 
-        app.add_systems(OnEnter(T::default()), crate::bevy_assets::setup);
+        app.add_systems(OnEnter(T::default()), crate::bevy_assets::setup::<T>);
         app.add_systems(
             Update,
             crate::bevy_assets::run::<T>.run_if(in_state(T::default())),
         );
-        app.add_systems(OnExit(T::default()), crate::bevy_assets::exit);
+        app.add_systems(OnExit(T::default()), crate::bevy_assets::exit::<T>);
+        app.add_systems(
+            StateTransition,
+            crate::bevy_assets::enter_asset_groups::<T>.before(apply_state_transition::<T>),
+        );
+
+        for (state, music) in self.state_music.iter().cloned() {
+            app.add_systems(
+                OnEnter(state),
+                move |store: Option<Res<crate::AssetStore>>,
+                      loaded_assets: Option<Res<crate::LoadedAssets>>,
+                      mut commands: Commands| {
+                    if let (Some(store), Some(loaded_assets)) = (store, loaded_assets) {
+                        store.play_music(&music, &mut commands, &loaded_assets);
+                    }
+                },
+            );
+        }
     }
 }
 
+#[derive(Clone)]
+pub(crate) struct MenuAssets {
+    pub(crate) main_menu: String,
+    pub(crate) game_over: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct LoadingScreen {
+    pub(crate) min_time: f32,
+    pub(crate) splash_image: Option<String>,
+}
+
 #[derive(Resource)]
 pub(crate) struct MenuResource<T> {
     pub(crate) menu_state: T,
     pub(crate) game_start_state: T,
     pub(crate) game_end_state: T,
+    pub(crate) menu_assets: MenuAssets,
+    pub(crate) load_failure: crate::LoadFailure<T>,
+    pub(crate) loading_screen: LoadingScreen,
+    pub(crate) asset_groups: Vec<(T, String)>,
+}
+
+impl<T> MenuResource<T>
+where
+    T: States,
+{
+    /// The asset groups bound to `state`.
+    pub(crate) fn groups_for(&self, state: &T) -> Vec<&str> {
+        self.asset_groups
+            .iter()
+            .filter(|(group_state, _)| group_state == state)
+            .map(|(_, group)| group.as_str())
+            .collect()
+    }
 }
 
 pub fn cleanup<T>(query: Query<Entity, With<T>>, mut commands: Commands)
@@ -37,6 +37,12 @@ impl PerFrameAnimation {
 #[derive(Resource)]
 pub struct Animations(HashMap<String, PerFrameAnimation>);
 
+impl Default for Animations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Animations {
     pub fn new() -> Self {
         Self(HashMap::new())
@@ -118,7 +124,12 @@ pub fn cycle_animations(
 macro_rules! spawn_animated_sprite {
     ($assets:expr, $commands:expr, $index:expr, $x:expr, $y:expr, $z:expr, $animation_name:expr, $($component:expr), *) => {
         $commands.spawn(SpriteSheetBundle {
-            texture_atlas: $assets.get_atlas_handle($index).unwrap(),
+            texture_atlas: $assets
+                .try_get_atlas_handle($index)
+                .unwrap_or_else(|error| {
+                    bevy::log::error!("{error}");
+                    Default::default()
+                }),
             transform: Transform::from_xyz($x, $y, $z),
             ..default()
         })
@@ -1,38 +1,389 @@
-use bevy::prelude::*;
+use bevy::{ecs::schedule::ScheduleLabel, prelude::*, utils::HashMap};
+use std::time::Duration;
+mod simulation;
+pub use simulation::*;
 
-//How frequently should the physics tick firs (ms)
-const PHYSICS_TICK_TIME: u128 = 33;
+/// Tunes the physics systems. [`PhysicsPlugin`] inserts the default; use
+/// [`PhysicsPlugin::with_config`] to change it. Speeds and accelerations
+/// are measured per tick.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use my_library::*;
+/// let config = PhysicsConfig::default()
+///     .with_tick_rate(60.0)
+///     .with_gravity(Vec3::ZERO)
+///     .with_damping(0.01);
+/// ```
+#[derive(Resource, Clone, Debug)]
+pub struct PhysicsConfig {
+    /// How long each physics tick lasts. Must be at least a nanosecond;
+    /// [`physics_clock`] panics on a zero tick time.
+    pub tick_time: Duration,
+    /// The most ticks [`physics_clock`] runs in one frame. A frame that
+    /// takes longer than this many ticks drops the time it can't catch up
+    /// on, rather than falling further behind with every frame. Must be at
+    /// least 1.
+    pub max_ticks_per_frame: u32,
+    /// Added to the velocity of everything with [`ApplyGravity`] each tick,
+    /// scaled by its [`GravityScale`].
+    pub gravity: Vec3,
+    /// No velocity is allowed to be faster than this.
+    pub max_speed: Option<f32>,
+    /// Slows velocities by `drag` times the square of their speed each tick,
+    /// like air resistance.
+    pub drag: f32,
+    /// The fraction of its velocity everything loses each tick.
+    pub damping: f32,
+    /// The fraction of its [`AngularVelocity`] everything loses each tick.
+    pub angular_damping: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            tick_time: Duration::from_millis(33),
+            max_ticks_per_frame: 5,
+            gravity: Vec3::new(0.0, -0.75, 0.0),
+            max_speed: None,
+            drag: 0.0,
+            damping: 0.0,
+            angular_damping: 0.0,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    /// Runs `ticks_per_second` physics ticks each second. Panics if
+    /// `ticks_per_second` isn't a positive, finite number, or is so large
+    /// that a tick would last less than a nanosecond.
+    pub fn with_tick_rate(mut self, ticks_per_second: f64) -> Self {
+        assert!(
+            ticks_per_second.is_finite() && ticks_per_second > 0.0,
+            "the physics tick rate must be a positive, finite number of ticks per second, not {ticks_per_second}"
+        );
+        let tick_time = Duration::from_secs_f64(1.0 / ticks_per_second);
+        assert!(
+            !tick_time.is_zero(),
+            "the physics tick rate must leave at least a nanosecond per tick, not {ticks_per_second} ticks per second"
+        );
+        self.tick_time = tick_time;
+        self
+    }
+
+    /// Panics if `max_ticks` is 0, which would stop the physics running.
+    pub fn with_max_ticks_per_frame(mut self, max_ticks: u32) -> Self {
+        assert!(
+            max_ticks > 0,
+            "the physics must be allowed at least one tick per frame"
+        );
+        self.max_ticks_per_frame = max_ticks;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vec3) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = Some(max_speed);
+        self
+    }
+
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn with_angular_damping(mut self, angular_damping: f32) -> Self {
+        self.angular_damping = angular_damping;
+        self
+    }
+
+    /// Applies damping, drag and the speed limit to a velocity.
+    fn resist(&self, velocity: Vec3) -> Vec3 {
+        let mut velocity = velocity * (1.0 - self.damping);
+        let speed = velocity.length();
+        if self.drag > 0.0 && speed > 0.0 {
+            let slowed = (speed - self.drag * speed * speed).max(0.0);
+            velocity *= slowed / speed;
+        }
+        if let Some(max_speed) = self.max_speed {
+            velocity = velocity.clamp_length_max(max_speed);
+        }
+        velocity
+    }
+}
+
+/// Adds the physics events and systems, and runs them in order while the
+/// game is in one of the plugin's states.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use my_library::*;
+/// # #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+/// # enum GamePhase { #[default] Loading, Playing }
+/// # #[derive(Component)] struct Player;
+/// # #[derive(Component)] struct Wall;
+/// App::new().add_plugins(
+///     PhysicsPlugin::new(GamePhase::Playing)
+///         .with_config(PhysicsConfig::default().with_max_speed(12.0))
+///         .with_collisions::<Player, Wall>(),
+/// );
+/// ```
+pub struct PhysicsPlugin<T> {
+    states: Vec<T>,
+    config: PhysicsConfig,
+    collisions: Vec<fn(&mut App)>,
+}
+
+impl<T> PhysicsPlugin<T>
+where
+    T: States,
+{
+    /// Runs physics while the game is in `state`.
+    pub fn new(state: T) -> Self {
+        Self {
+            states: vec![state],
+            config: PhysicsConfig::default(),
+            collisions: Vec::new(),
+        }
+    }
+
+    /// Also runs physics while the game is in `state`.
+    pub fn with_state(mut self, state: T) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    /// Sets the tick rate, gravity and resistance the physics systems use.
+    pub fn with_config(mut self, config: PhysicsConfig) -> Self {
+        self.config = config;
+        self
+    }
 
-#[derive(Default)]
-pub struct PhysicsTimer(u128);
+    /// Sends an [`OnCollision<A, B>`](crate::OnCollision) event each tick that
+    /// an entity with `A` overlaps one with `B`. Collisions are checked
+    /// against the `StaticQuadTree` resource, which the game inserts.
+    pub fn with_collisions<A, B>(mut self) -> Self
+    where
+        A: Component,
+        B: Component,
+    {
+        self.collisions.push(add_collisions::<A, B>);
+        self
+    }
+}
+
+fn add_collisions<A, B>(app: &mut App)
+where
+    A: Component,
+    B: Component,
+{
+    app.add_event::<crate::OnCollision<A, B>>();
+    app.add_systems(
+        PhysicsUpdate,
+        crate::check_collisions::<A, B>.in_set(PhysicsSet::Collide),
+    );
+}
+
+impl<T> Plugin for PhysicsPlugin<T>
+where
+    T: States,
+{
+    fn build(&self, app: &mut App) {
+        add_physics(app, &self.config, &self.collisions);
+
+        let states = self.states.clone();
+        let running = move |state: Option<Res<State<T>>>| {
+            state.is_some_and(|state| states.contains(state.get()))
+        };
+        app.configure_sets(Update, PhysicsSet::Interpolate.run_if(running.clone()));
+        app.add_systems(
+            Update,
+            (physics_clock, interpolate)
+                .chain()
+                .in_set(PhysicsSet::Interpolate),
+        );
+        // Otherwise impulses sent while the physics is stopped would pile
+        // up, and all apply at once when it starts again.
+        app.add_systems(Update, clear_impulses.run_if(not(running)));
+    }
+}
+
+/// Adds the events, resources and [`PhysicsUpdate`] systems that
+/// [`PhysicsPlugin`] and [`PhysicsSimulation`] share.
+fn add_physics(app: &mut App, config: &PhysicsConfig, collisions: &[fn(&mut App)]) {
+    app.add_event::<PhysicsTick>();
+    // Impulses are cleared by the physics tick rather than each frame,
+    // so that none are lost on frames without a tick.
+    app.init_resource::<Events<Impulse>>();
+    app.insert_resource(config.clone());
+    app.init_resource::<PhysicsTimer>();
+
+    app.configure_sets(
+        PhysicsUpdate,
+        (
+            PhysicsSet::Input,
+            PhysicsSet::Impulses,
+            PhysicsSet::Forces,
+            PhysicsSet::Integrate,
+            PhysicsSet::Collide,
+        )
+            .chain(),
+    );
+    app.add_systems(
+        PhysicsUpdate,
+        (
+            sum_impulses.in_set(PhysicsSet::Impulses),
+            (apply_gravity, apply_forces).in_set(PhysicsSet::Forces),
+            (apply_velocity, apply_angular_velocity).in_set(PhysicsSet::Integrate),
+        ),
+    );
+    for add_collisions in collisions {
+        add_collisions(app);
+    }
+}
+
+/// The steps of a physics tick, in the order they run. All but
+/// [`PhysicsSet::Interpolate`] are in the [`PhysicsUpdate`] schedule; add
+/// game systems to them there, e.g. reading the keyboard in
+/// [`PhysicsSet::Input`]:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use my_library::*;
+/// # fn flap() {}
+/// App::new().add_systems(PhysicsUpdate, flap.in_set(PhysicsSet::Input));
+/// ```
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PhysicsSet {
+    /// Game systems that send [`Impulse`]s.
+    Input,
+    /// [`sum_impulses`] changes velocities.
+    Impulses,
+    /// [`apply_gravity`] and [`apply_forces`] accelerate things.
+    Forces,
+    /// [`apply_velocity`] and [`apply_angular_velocity`] move things.
+    Integrate,
+    /// Collisions are checked where things have moved to.
+    Collide,
+    /// In `Update`: [`physics_clock`] runs the ticks that are due, then
+    /// [`interpolate`] moves each `Transform` to match. Order systems that
+    /// read a [`PhysicsPosition`]'s `Transform` after this.
+    Interpolate,
+}
+
+/// Systems that move things run here, once per physics tick, so that the
+/// game moves at the same speed whatever the frame rate.
+/// [`physics_clock`] runs the schedule as many times each frame as the
+/// time that has passed calls for. [`PhysicsPlugin`] orders it with
+/// [`PhysicsSet`].
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PhysicsUpdate;
+
+/// Time that has passed but not yet been simulated.
+#[derive(Resource, Default)]
+pub struct PhysicsTimer(Duration);
+
+impl PhysicsTimer {
+    /// Adds a frame's time, and returns how many ticks are due.
+    fn advance(&mut self, delta: Duration, config: &PhysicsConfig) -> u32 {
+        assert!(
+            !config.tick_time.is_zero(),
+            "PhysicsConfig::tick_time must be longer than zero"
+        );
+        assert!(
+            config.max_ticks_per_frame > 0,
+            "PhysicsConfig::max_ticks_per_frame must be at least 1"
+        );
+        self.0 += delta;
+        let mut ticks = 0;
+        while self.0 >= config.tick_time {
+            if ticks == config.max_ticks_per_frame {
+                // Too far behind to catch up; drop the backlog.
+                self.0 =
+                    Duration::from_nanos((self.0.as_nanos() % config.tick_time.as_nanos()) as u64);
+                break;
+            }
+            self.0 -= config.tick_time;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// How far through the next tick we are, from 0.0 to 1.0.
+    fn progress(&self, config: &PhysicsConfig) -> f32 {
+        self.0.as_secs_f32() / config.tick_time.as_secs_f32()
+    }
+}
 
 #[derive(Event)]
 pub struct PhysicsTick;
 
-pub fn physics_clock(
-    mut clock: Local<PhysicsTimer>,
-    time: Res<Time>,
-    mut on_tick: EventWriter<PhysicsTick>,
-    mut physics_position: Query<(&mut PhysicsPosition, &mut Transform)>,
+/// Runs [`PhysicsUpdate`] once for each physics tick that is due.
+pub fn physics_clock(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+    let config = world.resource::<PhysicsConfig>().clone();
+    let ticks = world.resource_mut::<PhysicsTimer>().advance(delta, &config);
+    for _ in 0..ticks {
+        physics_tick(world);
+    }
+}
+
+/// Moves each [`PhysicsPosition`]'s `Transform` between where it was at
+/// the start of the last tick and where it is now, by how far through the
+/// next tick we are. Things with an [`AngularVelocity`] are turned the same
+/// way.
+pub fn interpolate(
+    timer: Res<PhysicsTimer>,
+    config: Res<PhysicsConfig>,
+    mut physics_position: Query<(&PhysicsPosition, &mut Transform, Has<AngularVelocity>)>,
 ) {
-    let ms_since_last_call = time.delta().as_millis();
-    clock.0 += ms_since_last_call;
-    if clock.0 > PHYSICS_TICK_TIME {
-        clock.0 = 0;
-        physics_position.for_each_mut(|(mut pos, mut transform)| {
+    let frame_progress = timer.progress(&config);
+    physics_position.for_each_mut(|(pos, mut transform, spins)| {
+        transform.translation.x =
+            pos.start_frame.x + (pos.end_frame.x - pos.start_frame.x) * frame_progress;
+        transform.translation.y =
+            pos.start_frame.y + (pos.end_frame.y - pos.start_frame.y) * frame_progress;
+        if spins {
+            transform.rotation = Quat::from_rotation_z(
+                pos.start_rotation + (pos.end_rotation - pos.start_rotation) * frame_progress,
+            );
+        }
+    });
+}
+
+/// Starts each [`PhysicsPosition`]'s frame where the last one ended, sends
+/// [`PhysicsTick`] and runs [`PhysicsUpdate`].
+fn physics_tick(world: &mut World) {
+    let mut physics_position = world.query::<(
+        &mut PhysicsPosition,
+        Option<&mut Transform>,
+        Has<AngularVelocity>,
+    )>();
+    for (mut pos, transform, spins) in physics_position.iter_mut(world) {
+        if let Some(mut transform) = transform {
             transform.translation.x = pos.end_frame.x;
             transform.translation.y = pos.end_frame.y;
-            pos.start_frame = pos.end_frame
-        });
-        on_tick.send(PhysicsTick);
-    } else {
-        let frame_progress = clock.0 as f32 / PHYSICS_TICK_TIME as f32;
-        physics_position.for_each_mut(|(pos, mut transform)| {
-            transform.translation.x =
-                pos.start_frame.x + (pos.end_frame.x - pos.start_frame.x) * frame_progress;
-            transform.translation.y =
-                pos.start_frame.y + (pos.end_frame.y - pos.start_frame.y) * frame_progress;
-        })
+            if spins {
+                transform.rotation = Quat::from_rotation_z(pos.end_rotation);
+            }
+        }
+        pos.start_frame = pos.end_frame;
+        pos.start_rotation = pos.end_rotation;
+    }
+    world.send_event(PhysicsTick);
+    // Nothing to run if no systems were added to the schedule.
+    let _ = world.try_run_schedule(PhysicsUpdate);
+    if let Some(mut impulses) = world.get_resource_mut::<Events<Impulse>>() {
+        impulses.update();
     }
 }
 
@@ -49,34 +400,132 @@ impl Velocity {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         Self(Vec3 { x, y, z })
     }
+
+    pub fn get(&self) -> Vec3 {
+        self.0
+    }
 }
 
+/// Spins a [`PhysicsPosition`] by this many radians each tick, counter
+/// clockwise. Its `Transform` is turned to match.
+#[derive(Component, Default)]
+pub struct AngularVelocity(f32);
+
+impl AngularVelocity {
+    pub fn new(radians: f32) -> Self {
+        Self(radians)
+    }
+}
+
+/// How hard something is to spin: [`Torque`] and [`Impulse`]s applied
+/// off-centre are divided by it. Without one, it is 1.0.
+#[derive(Component)]
+pub struct RotationalInertia(pub f32);
+
+/// Added to an entity's [`Velocity`] every tick, until it is changed or
+/// removed.
+#[derive(Component, Default)]
+pub struct Force(pub Vec3);
+
+/// Added to an entity's [`AngularVelocity`] every tick (divided by its
+/// [`RotationalInertia`]), until it is changed or removed.
+#[derive(Component, Default)]
+pub struct Torque(pub f32);
+
+/// Changes the velocity of `target` on the next physics tick.
+///
+/// Each tick, an entity's velocity is set by the last absolute impulse sent
+/// to it (if any), then every other impulse is added to it. Impulses sent
+/// while the game isn't in one of [`PhysicsPlugin`]'s states are dropped.
 #[derive(Event)]
 pub struct Impulse {
     pub target: Entity,
     pub amount: Vec3,
     pub absolute: bool,
+    /// Where the impulse pushes, relative to the centre of `target`. Pushing
+    /// off-centre also spins an entity with an [`AngularVelocity`].
+    /// Absolute impulses ignore it.
+    pub point: Option<Vec2>,
+}
+
+impl Impulse {
+    /// Adds `amount` to the velocity of `target`.
+    pub fn new(target: Entity, amount: Vec3) -> Self {
+        Self {
+            target,
+            amount,
+            absolute: false,
+            point: None,
+        }
+    }
+
+    /// Sets the velocity of `target` to `amount`.
+    pub fn absolute(target: Entity, amount: Vec3) -> Self {
+        Self {
+            absolute: true,
+            ..Self::new(target, amount)
+        }
+    }
+
+    /// Pushes at `point`, relative to the centre of the target.
+    pub fn at(mut self, point: Vec2) -> Self {
+        self.point = Some(point);
+        self
+    }
 }
 
-pub fn sum_impulses(mut impulses: EventReader<Impulse>, mut velocities: Query<&mut Velocity>) {
+fn clear_impulses(mut impulses: ResMut<Events<Impulse>>) {
+    impulses.clear();
+}
+
+pub fn sum_impulses(
+    mut impulses: EventReader<Impulse>,
+    mut bodies: Query<(
+        &mut Velocity,
+        Option<&mut AngularVelocity>,
+        Option<&RotationalInertia>,
+    )>,
+) {
+    #[derive(Default)]
+    struct Total {
+        absolute: Option<Vec3>,
+        additive: Vec3,
+        spin: f32,
+    }
+
+    let mut totals: HashMap<Entity, Total> = HashMap::new();
     for impulse in impulses.read() {
-        if let Ok(mut velocity) = velocities.get_mut(impulse.target) {
-            if impulse.absolute {
-                velocity.0 = impulse.amount;
-                return;
-            } else {
-                velocity.0 += impulse.amount;
+        let total = totals.entry(impulse.target).or_default();
+        if impulse.absolute {
+            total.absolute = Some(impulse.amount);
+        } else {
+            total.additive += impulse.amount;
+            if let Some(point) = impulse.point {
+                total.spin += point.perp_dot(impulse.amount.truncate());
+            }
+        }
+    }
+
+    for (target, total) in totals {
+        if let Ok((mut velocity, angular_velocity, inertia)) = bodies.get_mut(target) {
+            velocity.0 = total.absolute.unwrap_or(velocity.0) + total.additive;
+            if let Some(mut angular_velocity) = angular_velocity {
+                angular_velocity.0 += total.spin / inertia.map_or(1.0, |inertia| inertia.0);
             }
         }
     }
 }
 
+/// Slows velocities by the [`PhysicsConfig`] damping and drag, limits
+/// them to its maximum speed, then moves each [`PhysicsPosition`].
 pub fn apply_velocity(
     mut tick: EventReader<PhysicsTick>,
-    mut movement: Query<(&Velocity, &mut PhysicsPosition)>,
+    config: Res<PhysicsConfig>,
+    mut movement: Query<(&mut Velocity, &mut PhysicsPosition)>,
 ) {
     for _tick in tick.read() {
-        movement.for_each_mut(|(velocity, mut position)| {
+        movement.for_each_mut(|(mut velocity, mut position)| {
+            velocity.0 = config.resist(velocity.0);
             position.end_frame += velocity.0.truncate();
         });
     }
@@ -85,21 +534,61 @@ pub fn apply_velocity(
 #[derive(Component)]
 pub struct ApplyGravity;
 
+/// Scales how strongly gravity pulls on an entity with [`ApplyGravity`].
+/// Without one, the scale is 1.0.
+#[derive(Component)]
+pub struct GravityScale(pub f32);
+
 pub fn apply_gravity(
     mut tick: EventReader<PhysicsTick>,
-    mut gravity: Query<&mut Velocity, With<ApplyGravity>>,
+    config: Res<PhysicsConfig>,
+    mut gravity: Query<(&mut Velocity, Option<&GravityScale>), With<ApplyGravity>>,
 ) {
     for _tick in tick.read() {
-        gravity.for_each_mut(|mut velocity| {
-            velocity.0.y -= 0.75;
+        gravity.for_each_mut(|(mut velocity, scale)| {
+            velocity.0 += config.gravity * scale.map_or(1.0, |scale| scale.0);
         })
     }
 }
 
+pub fn apply_forces(
+    mut tick: EventReader<PhysicsTick>,
+    mut forces: Query<(&mut Velocity, &Force)>,
+    mut torques: Query<(&mut AngularVelocity, &Torque, Option<&RotationalInertia>)>,
+) {
+    for _tick in tick.read() {
+        forces.for_each_mut(|(mut velocity, force)| {
+            velocity.0 += force.0;
+        });
+        torques.for_each_mut(|(mut angular_velocity, torque, inertia)| {
+            angular_velocity.0 += torque.0 / inertia.map_or(1.0, |inertia| inertia.0);
+        });
+    }
+}
+
+/// Slows each [`AngularVelocity`] by the [`PhysicsConfig`] angular damping,
+/// then turns its [`PhysicsPosition`].
+pub fn apply_angular_velocity(
+    mut tick: EventReader<PhysicsTick>,
+    config: Res<PhysicsConfig>,
+    mut movement: Query<(&mut AngularVelocity, &mut PhysicsPosition)>,
+) {
+    for _tick in tick.read() {
+        movement.for_each_mut(|(mut angular_velocity, mut position)| {
+            angular_velocity.0 *= 1.0 - config.angular_damping;
+            position.end_rotation += angular_velocity.0;
+        });
+    }
+}
+
 #[derive(Component)]
 pub struct PhysicsPosition {
     pub start_frame: Vec2,
     pub end_frame: Vec2,
+    /// Rotations are in radians, counter clockwise, and only change for
+    /// entities with an [`AngularVelocity`].
+    pub start_rotation: f32,
+    pub end_rotation: f32,
 }
 
 impl PhysicsPosition {
@@ -107,6 +596,205 @@ impl PhysicsPosition {
         Self {
             start_frame: start,
             end_frame: start,
+            start_rotation: 0.0,
+            end_rotation: 0.0,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A simulation with one body moving right at 1 unit per tick.
+    fn moving_body() -> (PhysicsSimulation, Entity) {
+        let mut simulation = PhysicsSimulation::new(PhysicsConfig::default());
+        let entity = simulation.spawn((
+            Velocity::new(1.0, 0.0, 0.0),
+            PhysicsPosition::new(Vec2::ZERO),
+            Transform::default(),
+        ));
+        (simulation, entity)
+    }
+
+    #[test]
+    fn test_physics_timer() {
+        let config = PhysicsConfig::default();
+        let mut timer = PhysicsTimer::default();
+        assert_eq!(timer.advance(Duration::from_millis(20), &config), 0);
+        assert_eq!(timer.advance(Duration::from_millis(20), &config), 1);
+        assert_eq!(timer.advance(Duration::from_millis(100), &config), 3);
+        assert_eq!(timer.0, Duration::from_millis(8));
+        // A long stall only catches up on a few ticks.
+        assert_eq!(
+            timer.advance(Duration::from_secs(10), &config),
+            config.max_ticks_per_frame
+        );
+        assert!(timer.0 < config.tick_time);
+
+        let config = PhysicsConfig::default().with_tick_rate(100.0);
+        assert_eq!(timer.advance(Duration::from_millis(100), &config), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "tick rate must be a positive, finite number")]
+    fn test_zero_tick_rate() {
+        PhysicsConfig::default().with_tick_rate(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "tick_time must be longer than zero")]
+    fn test_zero_tick_time() {
+        let config = PhysicsConfig {
+            tick_time: Duration::ZERO,
+            ..default()
+        };
+        PhysicsTimer::default().advance(Duration::from_millis(20), &config);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least a nanosecond per tick")]
+    fn test_huge_tick_rate() {
+        PhysicsConfig::default().with_tick_rate(1e10);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one tick per frame")]
+    fn test_zero_max_ticks() {
+        PhysicsConfig::default().with_max_ticks_per_frame(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_ticks_per_frame must be at least 1")]
+    fn test_zero_max_ticks_field() {
+        let config = PhysicsConfig {
+            max_ticks_per_frame: 0,
+            ..default()
+        };
+        PhysicsTimer::default().advance(Duration::from_millis(20), &config);
+    }
+
+    #[test]
+    fn test_frame_rate_independent() {
+        let (mut slow, slow_entity) = moving_body();
+        let (mut fast, fast_entity) = moving_body();
+        for _ in 0..10 {
+            slow.run_frame(Duration::from_millis(66));
+        }
+        for _ in 0..60 {
+            fast.run_frame(Duration::from_millis(11));
+        }
+        assert_eq!(slow.position(slow_entity), Some(Vec2::new(20.0, 0.0)));
+        assert_eq!(slow.position(slow_entity), fast.position(fast_entity));
+    }
+
+    #[test]
+    fn test_frame_rate_independent_after_stall() {
+        let (mut slow, slow_entity) = moving_body();
+        let (mut fast, fast_entity) = moving_body();
+        for stall in [false, true, false] {
+            if stall {
+                // Both fall a second behind, and both only catch up on
+                // max_ticks_per_frame ticks of it.
+                slow.run_frame(Duration::from_secs(1));
+                fast.run_frame(Duration::from_secs(1));
+            } else {
+                for _ in 0..10 {
+                    slow.run_frame(Duration::from_millis(66));
+                }
+                for _ in 0..60 {
+                    fast.run_frame(Duration::from_millis(11));
+                }
+            }
+            assert_eq!(slow.position(slow_entity), fast.position(fast_entity));
+        }
+        assert_eq!(slow.position(slow_entity), Some(Vec2::new(45.0, 0.0)));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let (mut simulation, entity) = moving_body();
+        simulation.run_frame(Duration::from_millis(33));
+        simulation.run_frame(Duration::from_millis(11));
+        let transform = simulation.world().get::<Transform>(entity).unwrap();
+        let position = simulation.world().get::<PhysicsPosition>(entity).unwrap();
+        assert_eq!(position.start_frame, Vec2::ZERO);
+        assert_eq!(position.end_frame, Vec2::new(1.0, 0.0));
+        assert!((transform.translation.x - 1.0 / 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_resistance() {
+        let config = PhysicsConfig::default();
+        assert_eq!(config.resist(Vec3::X * 100.0), Vec3::X * 100.0);
+        let config = config.with_max_speed(10.0);
+        assert_eq!(config.resist(Vec3::X * 100.0), Vec3::X * 10.0);
+        let config = config.with_damping(0.5);
+        assert_eq!(config.resist(Vec3::X * 4.0), Vec3::X * 2.0);
+        let config = PhysicsConfig::default().with_drag(0.1);
+        assert_eq!(config.resist(Vec3::Y * 2.0), Vec3::Y * 1.6);
+        // Drag never turns a body around.
+        assert_eq!(config.resist(Vec3::Y * 20.0), Vec3::ZERO);
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+    enum TestState {
+        #[default]
+        Menu,
+        Playing,
+    }
+
+    #[test]
+    fn test_physics_plugin() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_state::<TestState>()
+            .add_plugins(
+                PhysicsPlugin::new(TestState::Playing)
+                    .with_config(PhysicsConfig::default().with_gravity(Vec3::ZERO)),
+            );
+        let entity = app
+            .world
+            .spawn((
+                Velocity::default(),
+                PhysicsPosition::new(Vec2::ZERO),
+                Transform::default(),
+            ))
+            .id();
+        let run_frames = |app: &mut App, frames| {
+            for _ in 0..frames {
+                app.world
+                    .resource_mut::<Time>()
+                    .advance_by(Duration::from_millis(10));
+                app.update();
+            }
+        };
+        let position = |app: &App| app.world.get::<PhysicsPosition>(entity).unwrap().end_frame;
+
+        let impulses = |app: &App| app.world.resource::<Events<Impulse>>().len();
+
+        // Nothing moves outside the plugin's states, and impulses sent there
+        // are dropped rather than saved up for later.
+        app.world.send_event(Impulse::new(entity, Vec3::X));
+        run_frames(&mut app, 10);
+        assert_eq!(position(&app), Vec2::ZERO);
+        assert_eq!(impulses(&app), 0);
+
+        // The impulse waits for the first tick, and moves the entity in it.
+        app.world
+            .resource_mut::<NextState<TestState>>()
+            .set(TestState::Playing);
+        run_frames(&mut app, 1);
+        app.world.send_event(Impulse::new(entity, Vec3::X));
+        run_frames(&mut app, 3);
+        assert_eq!(position(&app), Vec2::X);
+
+        // Impulses still waiting for a tick are dropped on leaving.
+        app.world.send_event(Impulse::new(entity, Vec3::X));
+        app.world
+            .resource_mut::<NextState<TestState>>()
+            .set(TestState::Menu);
+        run_frames(&mut app, 1);
+        assert_eq!(impulses(&app), 0);
+    }
+}
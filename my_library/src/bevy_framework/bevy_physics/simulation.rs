@@ -0,0 +1,317 @@
+use super::{add_collisions, add_physics, physics_tick, PhysicsTick, PhysicsUpdate};
+use crate::{interpolate, physics_clock, PhysicsConfig, PhysicsPosition, PhysicsSet, Velocity};
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use std::time::Duration;
+
+/// Runs the physics systems a tick at a time, with no window, renderer or
+/// clock, so tests can check exactly where things end up.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use my_library::*;
+/// let mut simulation = PhysicsSimulation::new(PhysicsConfig::default());
+/// let dragon = simulation.spawn((
+///     Velocity::default(),
+///     ApplyGravity,
+///     PhysicsPosition::new(Vec2::ZERO),
+/// ));
+/// simulation.step(30);
+/// assert_eq!(simulation.position(dragon), Some(Vec2::new(0.0, -348.75)));
+/// ```
+pub struct PhysicsSimulation {
+    app: App,
+}
+
+impl PhysicsSimulation {
+    pub fn new(config: PhysicsConfig) -> Self {
+        let mut app = App::new();
+        app.insert_resource(Time::<()>::default());
+        add_physics(&mut app, &config, &[]);
+        Self { app }
+    }
+
+    /// Checks for collisions between entities with `A` and entities with
+    /// `B` each tick. Insert a `StaticQuadTree` with
+    /// [`with_resource`](Self::with_resource) for them to be checked in;
+    /// read them with [`drain_events`](Self::drain_events).
+    pub fn with_collisions<A, B>(mut self) -> Self
+    where
+        A: Component,
+        B: Component,
+    {
+        add_collisions::<A, B>(&mut self.app);
+        self
+    }
+
+    /// Runs game systems in one of the [`PhysicsSet`]s each tick.
+    pub fn with_systems<M>(mut self, set: PhysicsSet, systems: impl IntoSystemConfigs<M>) -> Self {
+        self.app.add_systems(PhysicsUpdate, systems.in_set(set));
+        self
+    }
+
+    pub fn with_resource<R: Resource>(mut self, resource: R) -> Self {
+        self.app.insert_resource(resource);
+        self
+    }
+
+    pub fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        self.app.world.spawn(bundle).id()
+    }
+
+    /// Sends an event, such as an [`Impulse`](crate::Impulse), for the next
+    /// tick to handle.
+    pub fn send<E: Event>(&mut self, event: E) {
+        self.app.world.send_event(event);
+    }
+
+    /// Runs `ticks` physics ticks.
+    pub fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            physics_tick(&mut self.app.world);
+            self.app
+                .world
+                .resource_mut::<Events<PhysicsTick>>()
+                .update();
+        }
+    }
+
+    /// Runs a frame that took `delta`, as the game would: the ticks that
+    /// are due, then [`interpolate`] to move each `Transform`.
+    pub fn run_frame(&mut self, delta: Duration) {
+        let world = &mut self.app.world;
+        world.resource_mut::<Time>().advance_by(delta);
+        physics_clock(world);
+        world.run_system_once(interpolate);
+        world.resource_mut::<Events<PhysicsTick>>().update();
+    }
+
+    /// Where `entity` is after the last tick.
+    pub fn position(&self, entity: Entity) -> Option<Vec2> {
+        self.app
+            .world
+            .get::<PhysicsPosition>(entity)
+            .map(|position| position.end_frame)
+    }
+
+    /// How far `entity` has turned after the last tick, in radians.
+    pub fn rotation(&self, entity: Entity) -> Option<f32> {
+        self.app
+            .world
+            .get::<PhysicsPosition>(entity)
+            .map(|position| position.end_rotation)
+    }
+
+    pub fn velocity(&self, entity: Entity) -> Option<Vec3> {
+        self.app
+            .world
+            .get::<Velocity>(entity)
+            .map(|velocity| velocity.0)
+    }
+
+    /// Takes the events of type `E` sent since they were last drained.
+    pub fn drain_events<E: Event>(&mut self) -> Vec<E> {
+        self.app
+            .world
+            .get_resource_mut::<Events<E>>()
+            .map_or_else(Vec::new, |mut events| events.drain().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        AngularVelocity, ApplyGravity, AxisAlignedBoundingBox, Force, GravityScale, Impulse,
+        OnCollision, RotationalInertia, StaticQuadTree, Torque,
+    };
+
+    #[derive(Component)]
+    struct Dragon;
+
+    #[derive(Component)]
+    struct Wall;
+
+    fn body(velocity: Vec3, position: Vec2) -> (Velocity, PhysicsPosition) {
+        (
+            Velocity::new(velocity.x, velocity.y, velocity.z),
+            PhysicsPosition::new(position),
+        )
+    }
+
+    fn weightless() -> PhysicsSimulation {
+        PhysicsSimulation::new(PhysicsConfig::default().with_gravity(Vec3::ZERO))
+    }
+
+    #[test]
+    fn test_gravity() {
+        let mut simulation = PhysicsSimulation::new(PhysicsConfig::default());
+        let dragon = simulation.spawn((body(Vec3::ZERO, Vec2::ZERO), ApplyGravity));
+        let floaty = simulation.spawn((
+            body(Vec3::ZERO, Vec2::ZERO),
+            ApplyGravity,
+            GravityScale(0.5),
+        ));
+        let weightless = simulation.spawn(body(Vec3::ZERO, Vec2::ZERO));
+
+        simulation.step(30);
+        assert_eq!(
+            simulation.velocity(dragon),
+            Some(Vec3::new(0.0, -22.5, 0.0))
+        );
+        assert_eq!(simulation.position(dragon), Some(Vec2::new(0.0, -348.75)));
+        assert_eq!(
+            simulation.velocity(floaty),
+            Some(Vec3::new(0.0, -11.25, 0.0))
+        );
+        assert_eq!(simulation.position(weightless), Some(Vec2::ZERO));
+
+        let mut simulation = PhysicsSimulation::new(PhysicsConfig::default().with_max_speed(10.0));
+        let dragon = simulation.spawn((body(Vec3::ZERO, Vec2::ZERO), ApplyGravity));
+        simulation.step(30);
+        assert_eq!(
+            simulation.velocity(dragon),
+            Some(Vec3::new(0.0, -10.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_impulses() {
+        let mut simulation = weightless();
+        let ball = simulation.spawn(body(Vec3::ZERO, Vec2::ZERO));
+
+        simulation.send(Impulse::new(ball, Vec3::X * 2.0));
+        simulation.step(1);
+        assert_eq!(simulation.position(ball), Some(Vec2::new(2.0, 0.0)));
+
+        // An impulse only applies once.
+        simulation.step(2);
+        assert_eq!(simulation.velocity(ball), Some(Vec3::X * 2.0));
+        assert_eq!(simulation.position(ball), Some(Vec2::new(6.0, 0.0)));
+
+        simulation.send(Impulse::absolute(ball, Vec3::Y));
+        simulation.step(1);
+        assert_eq!(simulation.velocity(ball), Some(Vec3::Y));
+        assert_eq!(simulation.position(ball), Some(Vec2::new(6.0, 1.0)));
+    }
+
+    #[test]
+    fn test_impulses_in_one_tick() {
+        let mut simulation = weightless();
+        let ball = simulation.spawn(body(Vec3::X * 5.0, Vec2::ZERO));
+        let other = simulation.spawn(body(Vec3::ZERO, Vec2::ZERO));
+
+        // Absolute impulses set the velocity before the others are added,
+        // whatever order they were sent in.
+        simulation.send(Impulse::new(ball, Vec3::Y));
+        simulation.send(Impulse::absolute(ball, Vec3::X));
+        simulation.send(Impulse::new(other, Vec3::X));
+        simulation.send(Impulse::new(ball, Vec3::Y));
+        simulation.step(1);
+        assert_eq!(simulation.velocity(ball), Some(Vec3::new(1.0, 2.0, 0.0)));
+        assert_eq!(simulation.velocity(other), Some(Vec3::X));
+
+        // The last absolute impulse wins.
+        simulation.send(Impulse::absolute(ball, Vec3::X));
+        simulation.send(Impulse::absolute(ball, Vec3::Y));
+        simulation.step(1);
+        assert_eq!(simulation.velocity(ball), Some(Vec3::Y));
+    }
+
+    #[test]
+    fn test_forces() {
+        let mut simulation =
+            PhysicsSimulation::new(PhysicsConfig::default().with_angular_damping(0.5));
+        let rocket = simulation.spawn((
+            body(Vec3::ZERO, Vec2::ZERO),
+            ApplyGravity,
+            Force(Vec3::Y),
+            AngularVelocity::default(),
+            Torque(4.0),
+            RotationalInertia(2.0),
+        ));
+        simulation.step(2);
+        assert_eq!(simulation.velocity(rocket), Some(Vec3::new(0.0, 0.5, 0.0)));
+        assert_eq!(simulation.position(rocket), Some(Vec2::new(0.0, 0.75)));
+        // Each tick adds 2 radians per tick, then loses half of it.
+        assert_eq!(simulation.rotation(rocket), Some(2.5));
+
+        simulation.world_mut().entity_mut(rocket).remove::<Torque>();
+        simulation.step(1);
+        assert_eq!(simulation.rotation(rocket), Some(3.25));
+    }
+
+    #[test]
+    fn test_impulse_at_point() {
+        let mut simulation = weightless();
+        let wheel = simulation.spawn((
+            body(Vec3::ZERO, Vec2::ZERO),
+            AngularVelocity::default(),
+            RotationalInertia(10.0),
+        ));
+        // Pushing up on the right hand side turns counter clockwise.
+        simulation.send(Impulse::new(wheel, Vec3::Y).at(Vec2::new(5.0, 0.0)));
+        simulation.step(2);
+        assert_eq!(simulation.velocity(wheel), Some(Vec3::Y));
+        assert_eq!(simulation.rotation(wheel), Some(1.0));
+
+        // Without an AngularVelocity, only the velocity changes.
+        let block = simulation.spawn(body(Vec3::ZERO, Vec2::ZERO));
+        simulation.send(Impulse::new(block, Vec3::Y).at(Vec2::new(5.0, 0.0)));
+        simulation.step(1);
+        assert_eq!(simulation.velocity(block), Some(Vec3::Y));
+        assert_eq!(simulation.rotation(block), Some(0.0));
+    }
+
+    #[test]
+    fn test_input_runs_before_integration() {
+        let mut simulation = PhysicsSimulation::new(PhysicsConfig::default()).with_systems(
+            PhysicsSet::Input,
+            |query: Query<Entity, With<Dragon>>, mut impulses: EventWriter<Impulse>| {
+                for target in query.iter() {
+                    impulses.send(Impulse::new(target, Vec3::Y));
+                }
+            },
+        );
+        let dragon = simulation.spawn((body(Vec3::ZERO, Vec2::ZERO), ApplyGravity, Dragon));
+        simulation.step(1);
+        assert_eq!(simulation.position(dragon), Some(Vec2::new(0.0, 0.25)));
+        simulation.step(1);
+        let position = simulation.world().get::<PhysicsPosition>(dragon).unwrap();
+        assert_eq!(position.start_frame, Vec2::new(0.0, 0.25));
+    }
+
+    #[test]
+    fn test_collisions() {
+        let mut simulation = weightless()
+            .with_collisions::<Dragon, Wall>()
+            .with_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4));
+        let dragon = simulation.spawn((
+            body(Vec3::X * 10.0, Vec2::new(-100.0, 0.0)),
+            AxisAlignedBoundingBox::new(32.0, 32.0),
+            Dragon,
+        ));
+        let wall = simulation.spawn((
+            body(Vec3::ZERO, Vec2::ZERO),
+            AxisAlignedBoundingBox::new(32.0, 32.0),
+            Wall,
+        ));
+
+        simulation.step(6);
+        assert!(simulation
+            .drain_events::<OnCollision<Dragon, Wall>>()
+            .is_empty());
+        simulation.step(1);
+        let collisions = simulation.drain_events::<OnCollision<Dragon, Wall>>();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].entity_a, dragon);
+        assert_eq!(collisions[0].entity_b, wall);
+    }
+}
@@ -15,12 +15,13 @@ pub(crate) fn setup<T>(
     T: States,
 {
     let current_state = state.get();
-    let menu_graphic = match current_state {
+    let menu_assets = &menu_resource.menu_assets;
+    let (tag, title, instructions) = match current_state {
         cs if menu_resource.menu_state == *cs => {
-            assets.get_handle("main_menu", &loaded_assets).unwrap()
+            (&menu_assets.main_menu, "Main Menu", "(P)lay or (Q)uit")
         }
         cs if menu_resource.game_end_state == *cs => {
-            assets.get_handle("game_over", &loaded_assets).unwrap()
+            (&menu_assets.game_over, "Game Over", "(M)ain Menu or (Q)uit")
         }
         _ => panic!("Unknown menu state"),
     };
@@ -28,13 +29,43 @@ pub(crate) fn setup<T>(
     commands
         .spawn(Camera2dBundle::default())
         .insert(MenuElement);
-    commands
-        .spawn(SpriteBundle {
-            texture: menu_graphic,
-            transform: Transform::from_xyz(0.0, 0.0, 1.0),
-            ..default()
-        })
-        .insert(MenuElement);
+    let menu_graphic = assets
+        .get_image(tag, &loaded_assets)
+        .filter(|_| !assets.is_placeholder(tag));
+    if let Some(menu_graphic) = menu_graphic {
+        commands
+            .spawn(SpriteBundle {
+                texture: menu_graphic,
+                transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                ..default()
+            })
+            .insert(MenuElement);
+    } else {
+        //No image registered for this menu, so generate a text screen instead.
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_sections([
+                    TextSection::new(
+                        format!("{title}\n\n"),
+                        TextStyle {
+                            font_size: 64.0,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        instructions,
+                        TextStyle {
+                            font_size: 32.0,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                ..default()
+            })
+            .insert(MenuElement);
+    }
 }
 
 pub(crate) fn run<T>(
@@ -61,3 +92,49 @@ pub(crate) fn run<T>(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{GameStatePlugin, LoadedAssets};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+    enum TestState {
+        #[default]
+        Menu,
+        Playing,
+        GameOver,
+    }
+
+    #[test]
+    fn test_menu_without_images_shows_text() {
+        let plugin = GameStatePlugin::new(TestState::Menu, TestState::Playing, TestState::GameOver)
+            .with_menu_images("title", "dead");
+        assert_eq!(plugin.required_assets(), ["title", "dead"]);
+        assert!(crate::AssetManager::empty()
+            .check_tags(&plugin.required_assets())
+            .is_err());
+
+        let mut world = World::new();
+        world.insert_resource(State::new(TestState::Menu));
+        world.insert_resource(MenuResource {
+            menu_state: TestState::Menu,
+            game_start_state: TestState::Playing,
+            game_end_state: TestState::GameOver,
+            menu_assets: plugin.menu_assets.clone(),
+            load_failure: crate::LoadFailure::Fallback,
+            loading_screen: plugin.loading_screen.clone(),
+            asset_groups: Vec::new(),
+        });
+        world.insert_resource(LoadedAssets::default());
+        world.insert_resource(AssetStore::default());
+        world.run_system_once(setup::<TestState>);
+
+        let mut text = world.query_filtered::<&Text, With<MenuElement>>();
+        let text = text.single(&world);
+        assert_eq!(text.sections[0].value, "Main Menu\n\n");
+        let mut sprites = world.query_filtered::<&Sprite, With<MenuElement>>();
+        assert_eq!(sprites.iter(&world).count(), 0);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fmt, str::FromStr};
+
+/// The most dice that dice notation may roll at once.
+pub const MAX_DICE: u32 = 1_000;
+/// The most sides that a die in dice notation may have.
+pub const MAX_DIE_SIDES: u32 = 1_000_000;
+
+/// `DiceRoll` describes a roll in classic dice notation, such as `3d6+2`:
+/// roll `n_dice` dice with `die_type` sides each, sum them and add `bonus`.
+///
+/// You rarely need to build one yourself---
+/// [`RandomNumberGenerator::roll_dice`](crate::RandomNumberGenerator::roll_dice)
+/// parses the notation for you. Parse once and keep the `DiceRoll` if you
+/// roll the same dice often.
+///
+/// Parsing rejects rolls of more than [`MAX_DICE`] dice or dice with more
+/// than [`MAX_DIE_SIDES`] sides. Totals that don't fit in an `i32` saturate.
+///
+/// ## Example
+///
+/// ```
+/// use my_library::DiceRoll;
+/// let roll: DiceRoll = "3d6+2".parse().unwrap();
+/// assert_eq!(roll, DiceRoll::new(3, 6, 2));
+/// assert_eq!(roll.min(), 5);
+/// assert_eq!(roll.max(), 20);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiceRoll {
+    pub n_dice: u32,
+    pub die_type: u32,
+    pub bonus: i32,
+}
+
+impl DiceRoll {
+    pub fn new(n_dice: u32, die_type: u32, bonus: i32) -> Self {
+        Self {
+            n_dice,
+            die_type,
+            bonus,
+        }
+    }
+
+    /// The lowest total this roll can produce.
+    pub fn min(&self) -> i32 {
+        self.total(self.n_dice as i64)
+    }
+
+    /// The highest total this roll can produce.
+    pub fn max(&self) -> i32 {
+        self.total((self.n_dice as i64).saturating_mul(self.die_type as i64))
+    }
+
+    /// Adds the bonus to the sum of the dice, saturating at the limits of
+    /// an `i32`.
+    pub(crate) fn total(&self, dice: i64) -> i32 {
+        dice.saturating_add(self.bonus as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+impl FromStr for DiceRoll {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let notation: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+        let invalid = || anyhow::Error::msg(format!("{s} is not valid dice notation"));
+
+        let (n_dice, rest) = notation.split_once('d').ok_or_else(invalid)?;
+        let n_dice = if n_dice.is_empty() {
+            1
+        } else {
+            n_dice.parse::<u32>().map_err(|_| invalid())?
+        };
+
+        let (die_type, bonus) = match rest.find(['+', '-']) {
+            Some(split) => {
+                let (die_type, bonus) = rest.split_at(split);
+                (die_type, bonus.parse::<i32>().map_err(|_| invalid())?)
+            }
+            None => (rest, 0),
+        };
+        let die_type = die_type.parse::<u32>().map_err(|_| invalid())?;
+
+        if n_dice == 0 || die_type == 0 {
+            return Err(invalid());
+        }
+        if n_dice > MAX_DICE || die_type > MAX_DIE_SIDES {
+            return Err(anyhow::Error::msg(format!(
+                "{s} rolls too many dice: at most {MAX_DICE}d{MAX_DIE_SIDES} is allowed"
+            )));
+        }
+        Ok(Self::new(n_dice, die_type, bonus))
+    }
+}
+
+impl fmt::Display for DiceRoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.n_dice, self.die_type)?;
+        match self.bonus {
+            0 => Ok(()),
+            bonus if bonus > 0 => write!(f, "+{bonus}"),
+            bonus => write!(f, "{bonus}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("3d6".parse::<DiceRoll>().unwrap(), DiceRoll::new(3, 6, 0));
+        assert_eq!("d20".parse::<DiceRoll>().unwrap(), DiceRoll::new(1, 20, 0));
+        assert_eq!("3d6+2".parse::<DiceRoll>().unwrap(), DiceRoll::new(3, 6, 2));
+        assert_eq!(
+            " 2D10 - 1 ".parse::<DiceRoll>().unwrap(),
+            DiceRoll::new(2, 10, -1)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "", "3", "3d", "d", "0d6", "3d0", "3x6", "3d6+", "3d6+x", "-3d6",
+        ] {
+            assert!(bad.parse::<DiceRoll>().is_err(), "{bad} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!("100000d100000".parse::<DiceRoll>().is_err());
+        assert!("1001d6".parse::<DiceRoll>().is_err());
+        assert!("1d1000001".parse::<DiceRoll>().is_err());
+
+        let roll: DiceRoll = "1000d1000000".parse().unwrap();
+        assert_eq!(roll.max(), 1_000_000_000);
+        let roll = DiceRoll::new(u32::MAX, u32::MAX, i32::MAX);
+        assert_eq!(roll.max(), i32::MAX);
+        let roll = DiceRoll::new(1, 6, i32::MIN);
+        assert_eq!(roll.min(), i32::MIN + 1);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for notation in ["3d6", "1d20", "3d6+2", "2d10-1"] {
+            let roll: DiceRoll = notation.parse().unwrap();
+            assert_eq!(roll.to_string(), notation);
+        }
+    }
+}
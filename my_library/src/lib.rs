@@ -5,6 +5,7 @@
 //! `my_library` includes:(3)
 //!
 //! * Random number generation facilities.(4)
+//! * Seeded value, Perlin and simplex noise.
 //!
 //! ## Feature Flags
 //!
@@ -13,24 +14,42 @@
 //! ### Random Number Generation
 //!
 //! * The `locking` feature enables interior mutability inside
-//! [`RandomNumberGenerator`], (5)
+//!   [`RandomNumberGenerator`], (5)
 //!   allowing it to be used as a resource (`Res<RandomNumberGenerator`)
-//! rather than requiring mutability (`ResMut<RandomNumberGenerator>`)
+//!   rather than requiring mutability (`ResMut<RandomNumberGenerator>`)
+//!   Systems that share one locked generator take turns; use [`SystemRng`]
+//!   or [`UnlockedRandomNumberGenerator`]s split from the shared one to let
+//!   them run in parallel.
 //! * You can control which random number generation algorithm is used by
-//! specifying *one* of:
+//!   specifying *one* of:
 //!    * `xorshift` to use the XorShift algorithm.
 //!    * `pcg` to use the PCG algorithm.
+//!
+//! ### Assets
+//!
+//! * The `hot_reload` feature watches the `assets` directory during
+//!   development and reloads files as they change. Sprite sheets are
+//!   rebuilt, so animations pick up new frames without a restart.
 
-#[cfg(not(feature = "locking"))]
 mod random;
 #[cfg(not(feature = "locking"))]
 pub use random::*;
+pub use random::{RandomNumberGenerator as UnlockedRandomNumberGenerator, RngSnapshot, RNG_ALGORITHM};
 
 #[cfg(feature = "locking")]
 mod random_locking;
 #[cfg(feature = "locking")]
 pub use random_locking::*;
 
+mod dice;
+pub use dice::*;
+
+mod random_plugin;
+pub use random_plugin::*;
+
+mod noise;
+pub use noise::*;
+
 /// [`RandomNumberGenerator`] wraps the `rand` crate. The `rand` crate
 /// is re-exported for your convenience.
 pub mod rand {
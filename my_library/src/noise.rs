@@ -0,0 +1,548 @@
+/// The algorithm a [`Noise`] generator uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseType {
+    /// Random values at each grid point, smoothly blended. Cheap, but
+    /// blocky-looking.
+    Value,
+    /// Ken Perlin's improved gradient noise.
+    Perlin,
+    /// Simplex noise: gradient noise on a triangular grid, with fewer
+    /// directional artifacts than Perlin noise.
+    Simplex,
+}
+
+/// `Noise` generates smooth, repeatable pseudo-random values over 1D, 2D or
+/// 3D space---useful for terrain, parallax variation or smoothing a
+/// sequence of random wall gaps. Every sample is in the range `-1.0..=1.0`.
+///
+/// A `Noise` is fully determined by its type, settings and seed: the same
+/// seed gives the same noise regardless of which random number generation
+/// backend is selected. Use [`RandomNumberGenerator::noise`](crate::RandomNumberGenerator::noise)
+/// to seed it from your game's generator.
+///
+/// Adding octaves sums several layers of noise (fractal Brownian motion),
+/// each at `lacunarity` times the frequency and `gain` times the amplitude
+/// of the previous layer.
+///
+/// ## Example
+///
+/// ```
+/// use my_library::*;
+/// let mut rng = RandomNumberGenerator::seeded(1);
+/// let terrain = rng.noise(NoiseType::Simplex).with_frequency(0.05).with_octaves(4);
+/// let height = terrain.get_2d(10.0, 20.0);
+/// assert!((-1.0..=1.0).contains(&height));
+/// ```
+#[derive(Clone)]
+pub struct Noise {
+    noise_type: NoiseType,
+    seed: u64,
+    permutation: [u8; 512],
+    frequency: f32,
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+}
+
+impl Noise {
+    pub fn new(noise_type: NoiseType, seed: u64) -> Self {
+        Self {
+            noise_type,
+            seed,
+            permutation: permutation_table(seed),
+            frequency: 1.0,
+            octaves: 1,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Scales input coordinates. Lower frequencies give broader features.
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Sets the number of layers summed together. Defaults to 1.
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    /// Sets how much the frequency grows per octave. Defaults to 2.
+    pub fn with_lacunarity(mut self, lacunarity: f32) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    /// Sets how much the amplitude shrinks per octave. Defaults to 0.5.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn noise_type(&self) -> NoiseType {
+        self.noise_type
+    }
+
+    pub fn get_1d(&self, x: f32) -> f32 {
+        self.fractal(|frequency| match self.noise_type {
+            NoiseType::Value => self.value_1d(x * frequency),
+            NoiseType::Perlin => self.perlin_1d(x * frequency),
+            NoiseType::Simplex => self.simplex_1d(x * frequency),
+        })
+    }
+
+    pub fn get_2d(&self, x: f32, y: f32) -> f32 {
+        self.fractal(|frequency| {
+            let (x, y) = (x * frequency, y * frequency);
+            match self.noise_type {
+                NoiseType::Value => self.value_2d(x, y),
+                NoiseType::Perlin => self.perlin_2d(x, y),
+                NoiseType::Simplex => self.simplex_2d(x, y),
+            }
+        })
+    }
+
+    pub fn get_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.fractal(|frequency| {
+            let (x, y, z) = (x * frequency, y * frequency, z * frequency);
+            match self.noise_type {
+                NoiseType::Value => self.value_3d(x, y, z),
+                NoiseType::Perlin => self.perlin_3d(x, y, z),
+                NoiseType::Simplex => self.simplex_3d(x, y, z),
+            }
+        })
+    }
+
+    /// Sums `octaves` layers of noise, normalized back into `-1..=1`.
+    fn fractal(&self, sample: impl Fn(f32) -> f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut total_amplitude = 0.0;
+        for _ in 0..self.octaves {
+            total += sample(frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        (total / total_amplitude).clamp(-1.0, 1.0)
+    }
+
+    fn hash(&self, i: i32) -> usize {
+        self.permutation[(i & 255) as usize] as usize
+    }
+
+    fn hash_2d(&self, x: i32, y: i32) -> usize {
+        self.permutation[self.hash(x) + (y & 255) as usize] as usize
+    }
+
+    fn hash_3d(&self, x: i32, y: i32, z: i32) -> usize {
+        self.permutation[self.hash_2d(x, y) + (z & 255) as usize] as usize
+    }
+
+    fn lattice_value(hash: usize) -> f32 {
+        hash as f32 / 127.5 - 1.0
+    }
+
+    fn value_1d(&self, x: f32) -> f32 {
+        let (x0, x) = cell(x);
+        let t = fade(x);
+        lerp(
+            t,
+            Self::lattice_value(self.hash(x0)),
+            Self::lattice_value(self.hash(x0.wrapping_add(1))),
+        )
+    }
+
+    fn value_2d(&self, x: f32, y: f32) -> f32 {
+        let ((x0, x), (y0, y)) = (cell(x), cell(y));
+        let (u, v) = (fade(x), fade(y));
+        let corner =
+            |dx, dy| Self::lattice_value(self.hash_2d(x0.wrapping_add(dx), y0.wrapping_add(dy)));
+        lerp(
+            v,
+            lerp(u, corner(0, 0), corner(1, 0)),
+            lerp(u, corner(0, 1), corner(1, 1)),
+        )
+    }
+
+    fn value_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        let ((x0, x), (y0, y), (z0, z)) = (cell(x), cell(y), cell(z));
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let corner = |dx, dy, dz| {
+            Self::lattice_value(self.hash_3d(
+                x0.wrapping_add(dx),
+                y0.wrapping_add(dy),
+                z0.wrapping_add(dz),
+            ))
+        };
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    fn perlin_1d(&self, x: f32) -> f32 {
+        let (x0, x) = cell(x);
+        let t = fade(x);
+        // A 1D gradient of +/-1 peaks at 0.5 halfway between grid points.
+        2.0 * lerp(
+            t,
+            grad_1d(self.hash(x0), x),
+            grad_1d(self.hash(x0.wrapping_add(1)), x - 1.0),
+        )
+    }
+
+    fn perlin_2d(&self, x: f32, y: f32) -> f32 {
+        let ((x0, x), (y0, y)) = (cell(x), cell(y));
+        let (u, v) = (fade(x), fade(y));
+        let corner = |dx: i32, dy: i32| {
+            grad_2d(
+                self.hash_2d(x0.wrapping_add(dx), y0.wrapping_add(dy)),
+                x - dx as f32,
+                y - dy as f32,
+            )
+        };
+        lerp(
+            v,
+            lerp(u, corner(0, 0), corner(1, 0)),
+            lerp(u, corner(0, 1), corner(1, 1)),
+        )
+    }
+
+    fn perlin_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        let ((x0, x), (y0, y), (z0, z)) = (cell(x), cell(y), cell(z));
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad_3d(
+                self.hash_3d(
+                    x0.wrapping_add(dx),
+                    y0.wrapping_add(dy),
+                    z0.wrapping_add(dz),
+                ),
+                x - dx as f32,
+                y - dy as f32,
+                z - dz as f32,
+            )
+        };
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    // The simplex implementations follow Stefan Gustavson's "Simplex noise
+    // demystified", including its output scaling factors.
+
+    fn simplex_1d(&self, x: f32) -> f32 {
+        let (i0, x0) = cell(x);
+        let x1 = x0 - 1.0;
+        let contribution = |hash: usize, x: f32| {
+            let t = (1.0 - x * x).powi(4);
+            t * simplex_grad_1d(hash, x)
+        };
+        0.395 * (contribution(self.hash(i0), x0) + contribution(self.hash(i0.wrapping_add(1)), x1))
+    }
+
+    fn simplex_2d(&self, x: f32, y: f32) -> f32 {
+        const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+        const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+        let skew = (x + y) * F2;
+        let (i, j) = ((x + skew).floor(), (y + skew).floor());
+        let unskew = (i + j) * G2;
+        let (x0, y0) = (x - (i - unskew), y - (j - unskew));
+        let (i, j) = (wrap(i), wrap(j));
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let (x1, y1) = (x0 - i1 as f32 + G2, y0 - j1 as f32 + G2);
+        let (x2, y2) = (x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2);
+
+        let contribution = |hash: usize, x: f32, y: f32| {
+            let t = 0.5 - x * x - y * y;
+            if t < 0.0 {
+                0.0
+            } else {
+                t.powi(4) * grad_2d(hash, x, y)
+            }
+        };
+        70.0 * (contribution(self.hash_2d(i, j), x0, y0)
+            + contribution(self.hash_2d(i.wrapping_add(i1), j.wrapping_add(j1)), x1, y1)
+            + contribution(self.hash_2d(i.wrapping_add(1), j.wrapping_add(1)), x2, y2))
+    }
+
+    fn simplex_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        const F3: f32 = 1.0 / 3.0;
+        const G3: f32 = 1.0 / 6.0;
+
+        let skew = (x + y + z) * F3;
+        let (i, j, k) = ((x + skew).floor(), (y + skew).floor(), (z + skew).floor());
+        let unskew = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - unskew), y - (j - unskew), z - (k - unskew));
+        let (i, j, k) = (wrap(i), wrap(j), wrap(k));
+
+        // Work out which of the six tetrahedra in the cube we are in.
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let offset = |(di, dj, dk): (i32, i32, i32), g: f32| {
+            (x0 - di as f32 + g, y0 - dj as f32 + g, z0 - dk as f32 + g)
+        };
+        let corners = [
+            ((0, 0, 0), (x0, y0, z0)),
+            ((i1, j1, k1), offset((i1, j1, k1), G3)),
+            ((i2, j2, k2), offset((i2, j2, k2), 2.0 * G3)),
+            ((1, 1, 1), offset((1, 1, 1), 3.0 * G3)),
+        ];
+
+        32.0 * corners
+            .iter()
+            .map(|((di, dj, dk), (x, y, z))| {
+                let t = 0.6 - x * x - y * y - z * z;
+                if t < 0.0 {
+                    0.0
+                } else {
+                    t.powi(4)
+                        * grad_3d(
+                            self.hash_3d(
+                                i.wrapping_add(*di),
+                                j.wrapping_add(*dj),
+                                k.wrapping_add(*dk),
+                            ),
+                            *x,
+                            *y,
+                            *z,
+                        )
+                }
+            })
+            .sum::<f32>()
+    }
+}
+
+/// Builds a shuffled permutation of 0..=255, repeated twice so that
+/// lookups can add two hashes without wrapping. The shuffle uses its own
+/// SplitMix64 generator so the table doesn't depend on the selected
+/// random number generation backend.
+fn permutation_table(seed: u64) -> [u8; 512] {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    let mut values: [u8; 256] = std::array::from_fn(|i| i as u8);
+    for i in (1..256).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        values.swap(i, j);
+    }
+    std::array::from_fn(|i| values[i & 255])
+}
+
+/// Splits `x` into the lattice cell it's in and how far into that cell it is.
+fn cell(x: f32) -> (i32, f32) {
+    let floor = x.floor();
+    (wrap(floor), x - floor)
+}
+
+/// Converts a whole number to a lattice coordinate. Only the low bits are
+/// hashed, so coordinates outside the `i32` range wrap around rather than
+/// all saturating to the same cell.
+fn wrap(floor: f32) -> i32 {
+    floor as i64 as i32
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn grad_1d(hash: usize, x: f32) -> f32 {
+    if hash & 1 == 0 {
+        x
+    } else {
+        -x
+    }
+}
+
+fn simplex_grad_1d(hash: usize, x: f32) -> f32 {
+    let gradient = 1.0 + (hash & 7) as f32;
+    if hash & 8 == 0 {
+        gradient * x
+    } else {
+        -gradient * x
+    }
+}
+
+fn grad_2d(hash: usize, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad_3d(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TYPES: [NoiseType; 3] = [NoiseType::Value, NoiseType::Perlin, NoiseType::Simplex];
+
+    fn samples() -> impl Iterator<Item = (f32, f32, f32)> {
+        (0..2000).map(|i| {
+            let i = i as f32;
+            (i * 0.173 - 50.0, i * 0.291 - 80.0, i * 0.057 + 3.0)
+        })
+    }
+
+    #[test]
+    fn test_noise_bounds() {
+        for noise_type in TYPES {
+            for octaves in [1, 4] {
+                let noise = Noise::new(noise_type, 1).with_octaves(octaves);
+                for (x, y, z) in samples() {
+                    for n in [noise.get_1d(x), noise.get_2d(x, y), noise.get_3d(x, y, z)] {
+                        assert!((-1.0..=1.0).contains(&n), "{noise_type:?} gave {n}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_noise_is_reproducible() {
+        for noise_type in TYPES {
+            let a = Noise::new(noise_type, 42).with_octaves(3);
+            let b = Noise::new(noise_type, 42).with_octaves(3);
+            let c = Noise::new(noise_type, 43).with_octaves(3);
+            let mut differs = false;
+            for (x, y, z) in samples() {
+                assert_eq!(a.get_3d(x, y, z), b.get_3d(x, y, z));
+                differs |= a.get_3d(x, y, z) != c.get_3d(x, y, z);
+            }
+            assert!(differs, "{noise_type:?} ignores its seed");
+        }
+    }
+
+    #[test]
+    fn test_noise_is_smooth() {
+        for noise_type in TYPES {
+            let noise = Noise::new(noise_type, 7);
+            for (x, y, _) in samples() {
+                let step = (noise.get_2d(x, y) - noise.get_2d(x + 0.001, y)).abs();
+                assert!(step < 0.05, "{noise_type:?} jumped by {step}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_noise_varies() {
+        for noise_type in TYPES {
+            let noise = Noise::new(noise_type, 7);
+            let (min, max) = samples()
+                .map(|(x, y, _)| noise.get_2d(x, y))
+                .fold((f32::MAX, f32::MIN), |(min, max), n| {
+                    (min.min(n), max.max(n))
+                });
+            assert!(max - min > 0.5, "{noise_type:?} is nearly flat");
+        }
+    }
+
+    #[test]
+    fn test_perlin_is_zero_on_grid() {
+        let noise = Noise::new(NoiseType::Perlin, 3);
+        for i in -10..10 {
+            let i = i as f32;
+            assert_eq!(noise.get_1d(i), 0.0);
+            assert_eq!(noise.get_2d(i, i * 2.0), 0.0);
+            assert_eq!(noise.get_3d(i, 1.0, -i), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_noise_far_from_origin() {
+        for noise_type in TYPES {
+            let noise = Noise::new(noise_type, 7);
+            for x in [i32::MAX as f32, i32::MIN as f32, 3e9, -3e9, 1e20, -1e20] {
+                for value in [noise.get_1d(x), noise.get_2d(x, -x), noise.get_3d(x, -x, x)] {
+                    assert!((-1.0..=1.0).contains(&value), "{noise_type:?} at {x}");
+                }
+            }
+            // The lattice repeats every 256 cells, so stepping past
+            // i32::MAX wraps around to the same noise as i32::MIN.
+            assert_eq!(noise.get_1d(i32::MAX as f32), noise.get_1d(i32::MIN as f32));
+        }
+    }
+
+    #[test]
+    fn test_permutation_table() {
+        let table = permutation_table(1);
+        let mut seen = [false; 256];
+        table[..256].iter().for_each(|n| seen[*n as usize] = true);
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(table[..256], table[256..]);
+    }
+}
@@ -1,7 +1,13 @@
+use crate::{DiceRoll, Noise, NoiseType};
 use rand::{Rng, SeedableRng, distributions::uniform::{SampleRange, SampleUniform}};
+use rand::{prelude::Distribution, seq::SliceRandom};
+use rand_distr::Normal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+// `StdRng` is ChaCha12 under the hood, but hides its state from serde.
+// Using `ChaCha12Rng` directly gives the same stream and can be saved.
 #[cfg(all(not(feature = "pcg"), not(feature = "xorshift")))]
-type RngCore = rand::prelude::StdRng;
+type RngCore = rand_chacha::ChaCha12Rng;
 
 #[cfg(feature = "pcg")]
 type RngCore = rand_pcg::Pcg64Mcg;
@@ -9,6 +15,19 @@ type RngCore = rand_pcg::Pcg64Mcg;
 #[cfg(feature = "xorshift")]
 type RngCore = rand_xorshift::XorShiftRng;
 
+#[cfg(all(not(feature = "pcg"), not(feature = "xorshift")))]
+const ALGORITHM: &str = "std";
+
+#[cfg(feature = "pcg")]
+const ALGORITHM: &str = "pcg";
+
+#[cfg(feature = "xorshift")]
+const ALGORITHM: &str = "xorshift";
+
+/// The name of the algorithm selected by feature flags: `pcg`, `xorshift`
+/// or `std` (ChaCha12, the same algorithm as `rand`'s `StdRng`).
+pub const RNG_ALGORITHM: &str = ALGORITHM;
+
 /// `RandomNumberGenerator` holds random number generation state, and offers 
 /// random number generation services to your program.
 /// 
@@ -21,7 +40,9 @@ type RngCore = rand_xorshift::XorShiftRng;
 /// is shared in Bevy with `ResMut<RandomNumberGenerator>`. If 
 /// you prefer interior mutability (and wish to use 
 /// `Res<RandomNumberGenerator>` instead), specify the `locking`
-/// feature flag.
+/// feature flag. This type is always available as
+/// `UnlockedRandomNumberGenerator`, for code that wants a generator of its
+/// own without a lock.
 /// 
 /// ## Example
 /// 
@@ -65,7 +86,7 @@ impl RandomNumberGenerator {
   /// use my_library::RandomNumberGenerator;
   /// let mut rng1 = RandomNumberGenerator::seeded(1);
   /// let mut rng2 = RandomNumberGenerator::seeded(1);
-  /// let results: (u32, u32) = ( rng1.next(), rng2.next() );
+  /// let results: (u32, u32) = ( rng1.random(), rng2.random() );
   /// assert_eq!(results.0, results.1);
   /// ```
   pub fn seeded(seed: u64) -> Self {
@@ -74,8 +95,57 @@ impl RandomNumberGenerator {
     }
   }
 
+  /// Creates an independent generator seeded from this one. Use it to
+  /// hand each thread (or each parallel task) its own generator; splits
+  /// are deterministic when this generator is seeded.
+  /// 
+  /// # Example
+  /// 
+  /// ```
+  /// use my_library::RandomNumberGenerator;
+  /// let mut rng = RandomNumberGenerator::seeded(1);
+  /// let mut child = rng.split();
+  /// let roll = child.range(1..=6);
+  /// ```
+  pub fn split(&mut self) -> Self {
+    Self::seeded(self.random())
+  }
+
+  /// Captures the exact internal state of the generator. Restoring the
+  /// snapshot later resumes the same stream of random numbers.
+  /// 
+  /// # Example
+  /// 
+  /// ```
+  /// use my_library::RandomNumberGenerator;
+  /// let mut rng = RandomNumberGenerator::new();
+  /// let snapshot = rng.snapshot();
+  /// let first: u32 = rng.random();
+  /// rng.restore(snapshot);
+  /// assert_eq!(first, rng.random::<u32>());
+  /// ```
+  pub fn snapshot(&self) -> RngSnapshot {
+    RngSnapshot {
+      rng: self.rng.clone(),
+    }
+  }
+
+  /// Replaces the generator's state with a previously captured snapshot.
+  /// 
+  /// # Arguments
+  /// 
+  /// * `snapshot` - the state to resume from.
+  pub fn restore(&mut self, snapshot: RngSnapshot) {
+    self.rng = snapshot.rng;
+  }
+
+  /// Creates a new `RandomNumberGenerator` that resumes from a snapshot.
+  pub fn from_snapshot(snapshot: RngSnapshot) -> Self {
+    Self { rng: snapshot.rng }
+  }
+
   /// Generates a new random number of the requested type.
-  pub fn next<T>(&mut self) -> T
+  pub fn random<T>(&mut self) -> T
   where rand::distributions::Standard: rand::prelude::Distribution<T>
   {
     self.rng.gen()
@@ -86,7 +156,7 @@ impl RandomNumberGenerator {
   /// # Arguments
   /// 
   /// * `range` - the range (inclusive or exclusive) within which to 
-  /// generate a random number.
+  ///   generate a random number.
   /// 
   /// # Example
   /// 
@@ -102,6 +172,118 @@ impl RandomNumberGenerator {
   {
     self.rng.gen_range(range)
   }
+
+  /// Rolls dice described in dice notation, such as `3d6+2`, and returns
+  /// the total.
+  /// 
+  /// # Arguments
+  /// 
+  /// * `dice` - the dice to roll: `[number of dice]d[sides][+/-bonus]`.
+  /// 
+  /// # Example
+  /// 
+  /// ```
+  /// use my_library::RandomNumberGenerator;
+  /// let mut rng = RandomNumberGenerator::new();
+  /// let damage = rng.roll_dice("3d6+2").unwrap();
+  /// assert!((5..=20).contains(&damage));
+  /// ```
+  pub fn roll_dice(&mut self, dice: &str) -> anyhow::Result<i32> {
+    Ok(self.roll(dice.parse()?))
+  }
+
+  /// Rolls a pre-parsed [`DiceRoll`] and returns the total.
+  pub fn roll(&mut self, dice: DiceRoll) -> i32 {
+    let sum = (0..dice.n_dice)
+      .map(|_| self.rng.gen_range(1..=dice.die_type) as i64)
+      .fold(0_i64, i64::saturating_add);
+    dice.total(sum)
+  }
+
+  /// Returns `true` with probability `p`. Values of `p` outside of
+  /// `0.0..=1.0` are clamped, so `chance(2.0)` is always `true`.
+  pub fn chance(&mut self, p: f64) -> bool {
+    self.rng.gen_bool(p.clamp(0.0, 1.0))
+  }
+
+  /// Samples a normal (gaussian) distribution.
+  /// 
+  /// # Arguments
+  /// 
+  /// * `mean` - the center of the distribution.
+  /// * `std_dev` - the standard deviation. Panics if it is negative or
+  ///   not finite.
+  pub fn normal(&mut self, mean: f32, std_dev: f32) -> f32 {
+    let normal = Normal::new(mean, std_dev).expect("invalid standard deviation");
+    normal.sample(&mut self.rng)
+  }
+
+  /// Picks a random item from a slice, or `None` if it is empty.
+  pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+    items.choose(&mut self.rng)
+  }
+
+  /// Picks `amount` distinct items from a slice, in random order. Returns
+  /// every item (shuffled) if the slice holds fewer than `amount`.
+  pub fn choose_multiple<'a, T>(&mut self, items: &'a [T], amount: usize) -> Vec<&'a T> {
+    items.choose_multiple(&mut self.rng, amount).collect()
+  }
+
+  /// Picks a random item from a slice, with the odds of each item
+  /// proportional to its weight.
+  /// 
+  /// Returns `None` if the slice is empty, every weight is zero, or any
+  /// weight is negative.
+  /// 
+  /// # Example
+  /// 
+  /// ```
+  /// use my_library::RandomNumberGenerator;
+  /// let mut rng = RandomNumberGenerator::new();
+  /// let loot = [("gold", 10.0), ("sword", 2.0), ("dragon egg", 0.1)];
+  /// let (item, _) = rng.choose_weighted(&loot, |(_, weight)| *weight).unwrap();
+  /// println!("You found: {item}");
+  /// ```
+  pub fn choose_weighted<'a, T>(
+    &mut self,
+    items: &'a [T],
+    weight: impl Fn(&T) -> f32,
+  ) -> Option<&'a T> {
+    items.choose_weighted(&mut self.rng, weight).ok()
+  }
+
+  /// Shuffles a slice in place.
+  pub fn shuffle<T>(&mut self, items: &mut [T]) {
+    items.shuffle(&mut self.rng);
+  }
+
+  /// Creates a [`Noise`] generator seeded from this generator.
+  pub fn noise(&mut self, noise_type: NoiseType) -> Noise {
+    Noise::new(noise_type, self.random())
+  }
+}
+
+/// `RngSnapshot` holds a copy of a [`RandomNumberGenerator`]'s internal
+/// state. It can be serialized (for example into a save game or replay
+/// file) and handed back to [`RandomNumberGenerator::restore`].
+/// 
+/// Snapshots are interchangeable between the locking and non-locking
+/// builds, but not between the `pcg`, `xorshift` and default backends.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RngSnapshot {
+  rng: RngCore,
+}
+
+impl Serialize for RandomNumberGenerator {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.snapshot().serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for RandomNumberGenerator {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    RngSnapshot::deserialize(deserializer).map(Self::from_snapshot)
+  }
 }
 
 #[cfg(test)]
@@ -143,10 +325,38 @@ mod test {
   }
 
   #[test]
-  fn test_next_types() {
+  fn test_random_types() {
     let mut rng = RandomNumberGenerator::new();
-    let _ : i32 = rng.next();
-    let _ = rng.next::<f32>();
+    let _ : i32 = rng.random();
+    let _ = rng.random::<f32>();
+  }
+
+  #[test]
+  fn test_snapshot_restore() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let _ = rng.random::<u64>();
+    let snapshot = rng.snapshot();
+    let first: Vec<u32> = (0..100).map(|_| rng.random()).collect();
+    rng.restore(snapshot.clone());
+    let second: Vec<u32> = (0..100).map(|_| rng.random()).collect();
+    assert_eq!(first, second);
+    let mut resumed = RandomNumberGenerator::from_snapshot(snapshot);
+    let third: Vec<u32> = (0..100).map(|_| resumed.random()).collect();
+    assert_eq!(first, third);
+  }
+
+  #[test]
+  fn test_serde_round_trip() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let _ = rng.range(0..100);
+    let saved = ron::to_string(&rng).unwrap();
+    let mut loaded: RandomNumberGenerator = ron::from_str(&saved).unwrap();
+    (0..1000).for_each(|_| {
+      assert_eq!(
+        rng.range(u32::MIN..u32::MAX),
+        loaded.range(u32::MIN..u32::MAX),
+      );
+    });
   }
 
   #[test]
@@ -155,24 +365,111 @@ mod test {
     for _ in 0..1000 {
       // fun bug: rng.range(f32::MIN .. f32::MAX)
       // Crashes with a "range overflow" error.
-      let n = rng.range(-3.40282347e+37_f32..3.40282347e+37_f32);
+      let n = rng.range(-3.402_823_5e37_f32..3.402_823_5e37_f32);
       assert!(n.is_finite());
       assert!(!n.is_infinite());
       assert!(!n.is_nan());
     }
   }
-}
 
-/// `Random` is a Bevy plugin that inserts a `RandomNumberGenerator` 
-/// resource into your application.
-/// 
-/// Once you add the plugin (with `App::new().add_plugin(Random)`),
-/// you can access a random number generator in systems with
-/// `rng: ResMut<RandomNumberGenerator>`.
-pub struct Random;
+  #[test]
+  fn test_dice_bounds() {
+    let mut rng = RandomNumberGenerator::new();
+    for _ in 0..1000 {
+      let n = rng.roll_dice("3d6+2").unwrap();
+      assert!(n >= 5);
+      assert!(n <= 20);
+    }
+    assert!(rng.roll_dice("banana").is_err());
+  }
+
+  #[test]
+  fn test_dice_distribution() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut results = [0; 19];
+    for _ in 0..10_000 {
+      results[rng.roll_dice("3d6").unwrap() as usize] += 1;
+    }
+    // Every total is reachable, and 10/11 are the most common.
+    assert!(results[3..=18].iter().all(|count| *count > 0));
+    assert!(results[10] > results[3] * 10);
+    assert!(results[11] > results[18] * 10);
+  }
+
+  #[test]
+  fn test_chance() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    assert!((0..1000).all(|_| !rng.chance(0.0)));
+    assert!((0..1000).all(|_| rng.chance(1.0)));
+    assert!((0..1000).all(|_| rng.chance(2.0)));
+    let hits = (0..10_000).filter(|_| rng.chance(0.25)).count();
+    assert!((2200..2800).contains(&hits));
+  }
+
+  #[test]
+  fn test_normal() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let samples: Vec<f32> = (0..10_000).map(|_| rng.normal(10.0, 2.0)).collect();
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    let variance = samples.iter().map(|n| (n - mean).powi(2)).sum::<f32>()
+      / samples.len() as f32;
+    assert!((mean - 10.0).abs() < 0.1);
+    assert!((variance.sqrt() - 2.0).abs() < 0.1);
+  }
 
-impl bevy::prelude::Plugin for Random {
-  fn build(&self, app: &mut bevy::prelude::App) {
-      app.insert_resource(RandomNumberGenerator::new());
+  #[test]
+  fn test_choose() {
+    let mut rng = RandomNumberGenerator::new();
+    let items = [1, 2, 3];
+    for _ in 0..1000 {
+      assert!(items.contains(rng.choose(&items).unwrap()));
+    }
+    assert!(rng.choose::<i32>(&[]).is_none());
+    let picked = rng.choose_multiple(&items, 2);
+    assert_eq!(picked.len(), 2);
+    assert_ne!(picked[0], picked[1]);
+    assert_eq!(rng.choose_multiple(&items, 10).len(), 3);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_choose_weighted() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let items = [("never", 0.0), ("rare", 1.0), ("common", 9.0)];
+    let mut rare = 0;
+    for _ in 0..10_000 {
+      match rng.choose_weighted(&items, |item| item.1).unwrap().0 {
+        "never" => panic!("picked an item with zero weight"),
+        "rare" => rare += 1,
+        _ => {}
+      }
+    }
+    assert!((800..1200).contains(&rare));
+    assert!(rng.choose_weighted(&[("none", 0.0)], |item| item.1).is_none());
+  }
+
+  #[test]
+  fn test_shuffle() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut items: Vec<u32> = (0..100).collect();
+    rng.shuffle(&mut items);
+    assert_ne!(items, (0..100).collect::<Vec<u32>>());
+    items.sort();
+    assert_eq!(items, (0..100).collect::<Vec<u32>>());
+  }
+
+  #[test]
+  fn test_split() {
+    let mut parent = (
+      RandomNumberGenerator::seeded(1),
+      RandomNumberGenerator::seeded(1),
+    );
+    let mut children = (parent.0.split(), parent.1.split());
+    let draws: (Vec<u32>, Vec<u32>) = (
+      (0..100).map(|_| children.0.random()).collect(),
+      (0..100).map(|_| children.1.random()).collect(),
+    );
+    assert_eq!(draws.0, draws.1);
+    let from_parent: Vec<u32> = (0..100).map(|_| parent.0.random()).collect();
+    assert_ne!(draws.0, from_parent);
+  }
+}
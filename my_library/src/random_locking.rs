@@ -1,17 +1,17 @@
-use rand::{Rng, SeedableRng, distributions::uniform::{SampleRange, SampleUniform}};
-use std::sync::Mutex;
-
-#[cfg(all(not(feature = "pcg"), not(feature = "xorshift")))]
-type RngCore = rand::prelude::StdRng;
-
-#[cfg(feature = "pcg")]
-type RngCore = rand_pcg::Pcg64Mcg;
-
-#[cfg(feature = "xorshift")]
-type RngCore = rand_xorshift::XorShiftRng;
+use crate::{DiceRoll, Noise, NoiseType, RngSnapshot, UnlockedRandomNumberGenerator};
+use rand::distributions::uniform::{SampleRange, SampleUniform};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
+/// With the `locking` feature, `RandomNumberGenerator` wraps an
+/// [`UnlockedRandomNumberGenerator`] in a mutex so that it can be shared
+/// as `Res<RandomNumberGenerator>`. Every call takes the lock; systems
+/// that draw a lot of numbers in parallel should use
+/// [`SystemRng`](crate::SystemRng) or [`split_unlocked`](Self::split_unlocked)
+/// instead.
+#[derive(bevy::prelude::Resource)]
 pub struct RandomNumberGenerator {
-  rng: Mutex<RngCore>,
+  rng: Mutex<UnlockedRandomNumberGenerator>,
 }
 
 impl Default for RandomNumberGenerator {
@@ -23,29 +23,129 @@ impl Default for RandomNumberGenerator {
 impl RandomNumberGenerator {
   pub fn new() -> Self {
     Self {
-      rng: Mutex::new(RngCore::from_entropy()),
+      rng: Mutex::new(UnlockedRandomNumberGenerator::new()),
     }
   }
 
   pub fn seeded(seed: u64) -> Self {
     Self {
-      rng: Mutex::new(RngCore::seed_from_u64(seed)),
+      rng: Mutex::new(UnlockedRandomNumberGenerator::seeded(seed)),
     }
   }
 
-  pub fn next<T>(&self) -> T
+  /// Creates an independent generator seeded from this one.
+  pub fn split(&self) -> Self {
+    Self::seeded(self.random())
+  }
+
+  /// Creates an independent generator seeded from this one, without a
+  /// lock of its own. Hand one to each thread (or each parallel task):
+  /// they never contend with each other, and splits are deterministic
+  /// when this generator is seeded.
+  pub fn split_unlocked(&self) -> UnlockedRandomNumberGenerator {
+    self.lock().split()
+  }
+
+  /// Captures the exact internal state of the generator. Restoring the
+  /// snapshot later resumes the same stream of random numbers.
+  pub fn snapshot(&self) -> RngSnapshot {
+    self.lock().snapshot()
+  }
+
+  /// Replaces the generator's state with a previously captured snapshot.
+  pub fn restore(&self, snapshot: RngSnapshot) {
+    self.lock().restore(snapshot);
+  }
+
+  /// Creates a new `RandomNumberGenerator` that resumes from a snapshot.
+  pub fn from_snapshot(snapshot: RngSnapshot) -> Self {
+    Self {
+      rng: Mutex::new(UnlockedRandomNumberGenerator::from_snapshot(snapshot)),
+    }
+  }
+
+  /// Locks the generator. A panic while the lock is held can't leave the
+  /// generator in an invalid state, so a poisoned lock is recovered
+  /// rather than propagated.
+  fn lock(&self) -> MutexGuard<'_, UnlockedRandomNumberGenerator> {
+    self.rng.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  pub fn random<T>(&self) -> T
   where rand::distributions::Standard: rand::prelude::Distribution<T>
   {
-    let mut lock = self.rng.lock().unwrap();
-    lock.gen()
+    self.lock().random()
   }
 
   pub fn range<T>(&self, range: impl SampleRange<T>) -> T
   where
     T: SampleUniform + PartialOrd,
   {
-    let mut lock = self.rng.lock().unwrap();
-    lock.gen_range(range)
+    self.lock().range(range)
+  }
+
+  /// Rolls dice described in dice notation, such as `3d6+2`, and returns
+  /// the total.
+  pub fn roll_dice(&self, dice: &str) -> anyhow::Result<i32> {
+    self.lock().roll_dice(dice)
+  }
+
+  /// Rolls a pre-parsed [`DiceRoll`] and returns the total.
+  pub fn roll(&self, dice: DiceRoll) -> i32 {
+    self.lock().roll(dice)
+  }
+
+  /// Returns `true` with probability `p`, clamped to `0.0..=1.0`.
+  pub fn chance(&self, p: f64) -> bool {
+    self.lock().chance(p)
+  }
+
+  /// Samples a normal (gaussian) distribution. Panics if `std_dev` is
+  /// negative or not finite.
+  pub fn normal(&self, mean: f32, std_dev: f32) -> f32 {
+    self.lock().normal(mean, std_dev)
+  }
+
+  /// Picks a random item from a slice, or `None` if it is empty.
+  pub fn choose<'a, T>(&self, items: &'a [T]) -> Option<&'a T> {
+    self.lock().choose(items)
+  }
+
+  /// Picks `amount` distinct items from a slice, in random order.
+  pub fn choose_multiple<'a, T>(&self, items: &'a [T], amount: usize) -> Vec<&'a T> {
+    self.lock().choose_multiple(items, amount)
+  }
+
+  /// Picks a random item from a slice, with the odds of each item
+  /// proportional to its weight. Returns `None` if no item can be picked.
+  pub fn choose_weighted<'a, T>(
+    &self,
+    items: &'a [T],
+    weight: impl Fn(&T) -> f32,
+  ) -> Option<&'a T> {
+    self.lock().choose_weighted(items, weight)
+  }
+
+  /// Shuffles a slice in place.
+  pub fn shuffle<T>(&self, items: &mut [T]) {
+    self.lock().shuffle(items)
+  }
+
+  /// Creates a [`Noise`] generator seeded from this generator.
+  pub fn noise(&self, noise_type: NoiseType) -> Noise {
+    self.lock().noise(noise_type)
+  }
+}
+
+impl Serialize for RandomNumberGenerator {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.snapshot().serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for RandomNumberGenerator {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    RngSnapshot::deserialize(deserializer).map(Self::from_snapshot)
   }
 }
 
@@ -88,10 +188,38 @@ mod test {
   }
 
   #[test]
-  fn test_next_types() {
-    let mut rng = RandomNumberGenerator::new();
-    let _ : i32 = rng.next();
-    let _ = rng.next::<f32>();
+  fn test_random_types() {
+    let rng = RandomNumberGenerator::new();
+    let _ : i32 = rng.random();
+    let _ = rng.random::<f32>();
+  }
+
+  #[test]
+  fn test_snapshot_restore() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let _ = rng.random::<u64>();
+    let snapshot = rng.snapshot();
+    let first: Vec<u32> = (0..100).map(|_| rng.random()).collect();
+    rng.restore(snapshot.clone());
+    let second: Vec<u32> = (0..100).map(|_| rng.random()).collect();
+    assert_eq!(first, second);
+    let resumed = RandomNumberGenerator::from_snapshot(snapshot);
+    let third: Vec<u32> = (0..100).map(|_| resumed.random()).collect();
+    assert_eq!(first, third);
+  }
+
+  #[test]
+  fn test_serde_round_trip() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let _ = rng.range(0..100);
+    let saved = ron::to_string(&rng).unwrap();
+    let loaded: RandomNumberGenerator = ron::from_str(&saved).unwrap();
+    (0..1000).for_each(|_| {
+      assert_eq!(
+        rng.range(u32::MIN..u32::MAX),
+        loaded.range(u32::MIN..u32::MAX),
+      );
+    });
   }
 
   #[test]
@@ -100,18 +228,125 @@ mod test {
     for _ in 0..1000 {
       // fun bug: rng.range(f32::MIN .. f32::MAX)
       // Crashes with a "range overflow" error.
-      let n = rng.range(-3.40282347e+37_f32..3.40282347e+37_f32);
+      let n = rng.range(-3.402_823_5e37_f32..3.402_823_5e37_f32);
       assert!(n.is_finite());
       assert!(!n.is_infinite());
       assert!(!n.is_nan());
     }
   }
-}
 
-pub struct Random;
+  #[test]
+  fn test_dice_bounds() {
+    let rng = RandomNumberGenerator::new();
+    for _ in 0..1000 {
+      let n = rng.roll_dice("3d6+2").unwrap();
+      assert!(n >= 5);
+      assert!(n <= 20);
+    }
+    assert!(rng.roll_dice("banana").is_err());
+  }
+
+  #[test]
+  fn test_dice_distribution() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let mut results = [0; 19];
+    for _ in 0..10_000 {
+      results[rng.roll_dice("3d6").unwrap() as usize] += 1;
+    }
+    // Every total is reachable, and 10/11 are the most common.
+    assert!(results[3..=18].iter().all(|count| *count > 0));
+    assert!(results[10] > results[3] * 10);
+    assert!(results[11] > results[18] * 10);
+  }
 
-impl bevy::prelude::Plugin for Random {
-  fn build(&self, app: &mut bevy::prelude::App) {
-      app.insert_resource(RandomNumberGenerator::new());
+  #[test]
+  fn test_chance() {
+    let rng = RandomNumberGenerator::seeded(1);
+    assert!((0..1000).all(|_| !rng.chance(0.0)));
+    assert!((0..1000).all(|_| rng.chance(1.0)));
+    assert!((0..1000).all(|_| rng.chance(2.0)));
+    let hits = (0..10_000).filter(|_| rng.chance(0.25)).count();
+    assert!((2200..2800).contains(&hits));
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_normal() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let samples: Vec<f32> = (0..10_000).map(|_| rng.normal(10.0, 2.0)).collect();
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    let variance = samples.iter().map(|n| (n - mean).powi(2)).sum::<f32>()
+      / samples.len() as f32;
+    assert!((mean - 10.0).abs() < 0.1);
+    assert!((variance.sqrt() - 2.0).abs() < 0.1);
+  }
+
+  #[test]
+  fn test_choose() {
+    let rng = RandomNumberGenerator::new();
+    let items = [1, 2, 3];
+    for _ in 0..1000 {
+      assert!(items.contains(rng.choose(&items).unwrap()));
+    }
+    assert!(rng.choose::<i32>(&[]).is_none());
+    let picked = rng.choose_multiple(&items, 2);
+    assert_eq!(picked.len(), 2);
+    assert_ne!(picked[0], picked[1]);
+    assert_eq!(rng.choose_multiple(&items, 10).len(), 3);
+  }
+
+  #[test]
+  fn test_choose_weighted() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let items = [("never", 0.0), ("rare", 1.0), ("common", 9.0)];
+    let mut rare = 0;
+    for _ in 0..10_000 {
+      match rng.choose_weighted(&items, |item| item.1).unwrap().0 {
+        "never" => panic!("picked an item with zero weight"),
+        "rare" => rare += 1,
+        _ => {}
+      }
+    }
+    assert!((800..1200).contains(&rare));
+    assert!(rng.choose_weighted(&[("none", 0.0)], |item| item.1).is_none());
+  }
+
+  #[test]
+  fn test_shuffle() {
+    let rng = RandomNumberGenerator::seeded(1);
+    let mut items: Vec<u32> = (0..100).collect();
+    rng.shuffle(&mut items);
+    assert_ne!(items, (0..100).collect::<Vec<u32>>());
+    items.sort();
+    assert_eq!(items, (0..100).collect::<Vec<u32>>());
+  }
+
+  #[test]
+  fn test_split() {
+    let parent = (
+      RandomNumberGenerator::seeded(1),
+      RandomNumberGenerator::seeded(1),
+    );
+    let children = (parent.0.split(), parent.1.split());
+    let draws: (Vec<u32>, Vec<u32>) = (
+      (0..100).map(|_| children.0.random()).collect(),
+      (0..100).map(|_| children.1.random()).collect(),
+    );
+    assert_eq!(draws.0, draws.1);
+    let from_parent: Vec<u32> = (0..100).map(|_| parent.0.random()).collect();
+    assert_ne!(draws.0, from_parent);
+  }
+
+  #[test]
+  fn test_poisoned_lock_recovers() {
+    let rng = std::sync::Arc::new(RandomNumberGenerator::seeded(1));
+    let poisoner = rng.clone();
+    let _ = std::thread::spawn(move || {
+      let _lock = poisoner.lock();
+      panic!("poison the lock");
+    })
+    .join();
+    assert!(rng.rng.is_poisoned());
+    let n = rng.range(1..10);
+    assert!((1..10).contains(&n));
+  }
+}
@@ -0,0 +1,387 @@
+use crate::{RandomNumberGenerator, UnlockedRandomNumberGenerator, RNG_ALGORITHM};
+use bevy::{
+    ecs::{
+        component::Tick,
+        system::{ReadOnlySystemParam, SystemMeta, SystemParam},
+        world::unsafe_world_cell::UnsafeWorldCell,
+    },
+    log,
+    prelude::*,
+    utils::HashMap,
+};
+use std::ops::{Deref, DerefMut};
+
+/// `Random` is a Bevy plugin that inserts a `RandomNumberGenerator`
+/// resource into your application.
+///
+/// Once you add the plugin (with `App::new().add_plugins(Random::new())`),
+/// you can access a random number generator in systems with
+/// `rng: ResMut<RandomNumberGenerator>` (or `Res<RandomNumberGenerator>`
+/// with the `locking` feature).
+///
+/// The plugin also inserts a [`RandomStreams`] resource. Streams are
+/// separate generators, each derived from the same master seed and a
+/// name. Drawing from one stream never changes what another produces,
+/// so cosmetic randomness can't disturb your level layout.
+///
+/// The master seed comes from entropy unless you provide one. It is
+/// logged at startup, so a bug report can quote it and the exact run can
+/// be reproduced. Sources are checked in order: the command-line flag
+/// (`with_seed_from_args`), then the environment variable
+/// (`with_seed_from_env`), then `with_seed`.
+///
+/// ## Example
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use my_library::*;
+///
+/// fn build_level(mut streams: ResMut<RandomStreams>) {
+///     let gap = streams.stream("level").range(-5..5);
+/// }
+///
+/// App::new()
+///     .add_plugins(
+///         Random::new()
+///             .with_stream("level")
+///             .with_stream("fx")
+///             .with_seed_from_env("MY_GAME_SEED")
+///             .with_seed_from_args("--seed"),
+///     )
+///     .add_systems(Update, build_level);
+/// ```
+#[derive(Clone, Default)]
+pub struct Random {
+    streams: Vec<String>,
+    seed: Option<u64>,
+    seed_env: Option<String>,
+    seed_arg: Option<String>,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a named stream when the plugin starts. Undeclared streams
+    /// are created on first use by [`RandomStreams::stream`]; declaring
+    /// them makes them available through [`RandomStreams::get`] as well.
+    pub fn with_stream<S: ToString>(mut self, name: S) -> Self {
+        self.streams.push(name.to_string());
+        self
+    }
+
+    /// Uses a fixed master seed, so every run produces the same numbers.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Reads the master seed from an environment variable, if it is set.
+    pub fn with_seed_from_env<S: ToString>(mut self, variable: S) -> Self {
+        self.seed_env = Some(variable.to_string());
+        self
+    }
+
+    /// Reads the master seed from a command-line flag, accepting both
+    /// `--seed 12345` and `--seed=12345`.
+    pub fn with_seed_from_args<S: ToString>(mut self, flag: S) -> Self {
+        self.seed_arg = Some(flag.to_string());
+        self
+    }
+
+    fn master_seed(&self) -> u64 {
+        let from_args = self.seed_arg.as_ref().and_then(|flag| {
+            // Arguments that aren't valid UTF-8 can't be the seed flag,
+            // and `std::env::args` would panic on them.
+            let args = std::env::args_os().filter_map(|arg| arg.into_string().ok());
+            seed_from_args(args, flag)
+        });
+        let from_env = self
+            .seed_env
+            .as_ref()
+            .and_then(|variable| parse_seed(variable, &std::env::var(variable).ok()?));
+        from_args
+            .or(from_env)
+            .or(self.seed)
+            .unwrap_or_else(|| RandomNumberGenerator::new().random::<u64>())
+    }
+}
+
+impl Plugin for Random {
+    fn build(&self, app: &mut App) {
+        let master_seed = self.master_seed();
+        log::info!("Random number generator ({RNG_ALGORITHM}) seed: {master_seed}");
+        let mut streams = RandomStreams::new(master_seed);
+        self.streams.iter().for_each(|name| {
+            streams.stream(name);
+        });
+        app.insert_resource(RandomNumberGenerator::seeded(master_seed));
+        app.insert_resource(streams);
+    }
+}
+
+/// `RandomStreams` holds a set of named [`RandomNumberGenerator`]s, each
+/// seeded from a master seed and the stream's name. A given master seed
+/// and name always produce the same stream, no matter which other
+/// streams exist or how much they have been used.
+#[derive(Resource)]
+pub struct RandomStreams {
+    master_seed: u64,
+    streams: HashMap<String, RandomNumberGenerator>,
+}
+
+impl RandomStreams {
+    pub fn new(master_seed: u64) -> Self {
+        Self {
+            master_seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// The seed every stream is derived from.
+    pub fn master_seed(&self) -> u64 {
+        self.master_seed
+    }
+
+    /// Returns the named stream, creating it if it doesn't exist yet.
+    pub fn stream(&mut self, name: &str) -> &mut RandomNumberGenerator {
+        let master_seed = self.master_seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| RandomNumberGenerator::seeded(stream_seed(master_seed, name)))
+    }
+
+    /// Returns the named stream if it has already been created.
+    pub fn get(&self, name: &str) -> Option<&RandomNumberGenerator> {
+        self.streams.get(name)
+    }
+}
+
+/// `SystemRng` is a system parameter that gives each system its own
+/// [`UnlockedRandomNumberGenerator`]. Systems that use it never wait on a
+/// shared resource or a lock, so Bevy is free to run them in parallel.
+///
+/// Each system's generator is the [`RandomStreams`] stream named after
+/// the system, so results depend on the master seed and the system's
+/// name---not on which thread runs first. Without the [`Random`] plugin
+/// the generator is seeded from entropy. Adding the same function as
+/// more than one system gives each copy the same stream.
+///
+/// The system's name is its full path, such as `my_game::enemies::spawn`,
+/// so moving or renaming the function or its module changes its stream.
+/// Systems whose sequences must survive refactoring (for replays or
+/// saved games) should use a named stream from [`RandomStreams`] instead.
+///
+/// ## Example
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use my_library::*;
+///
+/// fn sparkle(mut rng: SystemRng) {
+///     let brightness = rng.range(0.5..1.0);
+/// }
+///
+/// App::new()
+///     .add_plugins(Random::new().with_seed(12345))
+///     .add_systems(Update, sparkle);
+/// ```
+pub struct SystemRng<'s> {
+    rng: &'s mut UnlockedRandomNumberGenerator,
+}
+
+impl Deref for SystemRng<'_> {
+    type Target = UnlockedRandomNumberGenerator;
+
+    fn deref(&self) -> &Self::Target {
+        self.rng
+    }
+}
+
+impl DerefMut for SystemRng<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.rng
+    }
+}
+
+// SAFETY: only system-local state is accessed.
+unsafe impl SystemParam for SystemRng<'_> {
+    type State = UnlockedRandomNumberGenerator;
+    type Item<'w, 's> = SystemRng<'s>;
+
+    fn init_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::State {
+        match world.get_resource::<RandomStreams>() {
+            Some(streams) => UnlockedRandomNumberGenerator::seeded(stream_seed(
+                streams.master_seed,
+                system_meta.name(),
+            )),
+            None => UnlockedRandomNumberGenerator::new(),
+        }
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _system_meta: &SystemMeta,
+        _world: UnsafeWorldCell<'w>,
+        _change_tick: Tick,
+    ) -> Self::Item<'w, 's> {
+        SystemRng { rng: state }
+    }
+}
+
+// SAFETY: SystemRng doesn't access the world at all.
+unsafe impl ReadOnlySystemParam for SystemRng<'_> {}
+
+/// Finds `flag` in a list of command-line arguments and parses the value
+/// that follows it.
+fn seed_from_args(args: impl Iterator<Item = String>, flag: &str) -> Option<u64> {
+    let prefix = format!("{flag}=");
+    let mut args = args.skip_while(|arg| arg != flag && !arg.starts_with(&prefix));
+    let value = match args.next()? {
+        arg if arg == flag => args.next()?,
+        arg => arg[prefix.len()..].to_string(),
+    };
+    parse_seed(flag, &value)
+}
+
+fn parse_seed(source: &str, value: &str) -> Option<u64> {
+    match value.trim().parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            log::warn!("Ignoring {source}: \"{value}\" is not a valid seed");
+            None
+        }
+    }
+}
+
+/// Derives a stream's seed by mixing an FNV-1a hash of its name into the
+/// master seed, then scrambling with SplitMix64. Both are fixed algorithms,
+/// so seeds stay stable across platforms and compiler versions.
+fn stream_seed(master_seed: u64, name: &str) -> u64 {
+    let name_hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let mut z = (master_seed ^ name_hash).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn draw(streams: &mut RandomStreams, name: &str) -> Vec<u32> {
+        (0..100)
+            .map(|_| streams.stream(name).range(0..1000))
+            .collect()
+    }
+
+    #[test]
+    fn test_streams_are_reproducible() {
+        let mut a = RandomStreams::new(12345);
+        let mut b = RandomStreams::new(12345);
+        assert_eq!(draw(&mut a, "level"), draw(&mut b, "level"));
+    }
+
+    #[test]
+    fn test_streams_are_independent() {
+        let mut plain = RandomStreams::new(12345);
+        let mut busy = RandomStreams::new(12345);
+        draw(&mut busy, "fx");
+        let level = draw(&mut plain, "level");
+        assert_eq!(level, draw(&mut busy, "level"));
+        assert_ne!(level, draw(&mut plain, "fx"));
+    }
+
+    #[test]
+    fn test_master_seed_changes_streams() {
+        let mut a = RandomStreams::new(1);
+        let mut b = RandomStreams::new(2);
+        assert_ne!(draw(&mut a, "level"), draw(&mut b, "level"));
+    }
+
+    #[test]
+    fn test_seed_from_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let parse = |a: &[&str]| seed_from_args(args(a).into_iter(), "--seed");
+        assert_eq!(parse(&["game", "--seed", "12345"]), Some(12345));
+        assert_eq!(parse(&["game", "--seed=12345"]), Some(12345));
+        assert_eq!(
+            parse(&["game", "--windowed", "--seed", "7", "--fast"]),
+            Some(7)
+        );
+        assert_eq!(parse(&["game"]), None);
+        assert_eq!(parse(&["game", "--seed"]), None);
+        assert_eq!(parse(&["game", "--seed", "banana"]), None);
+        assert_eq!(parse(&["game", "--seeds", "12"]), None);
+    }
+
+    #[test]
+    fn test_seed_from_env() {
+        std::env::set_var("MY_LIBRARY_TEST_SEED", "42");
+        let random = Random::new()
+            .with_seed(7)
+            .with_seed_from_env("MY_LIBRARY_TEST_SEED");
+        assert_eq!(random.master_seed(), 42);
+        let random = Random::new()
+            .with_seed(7)
+            .with_seed_from_env("MY_LIBRARY_TEST_SEED_UNSET");
+        assert_eq!(random.master_seed(), 7);
+    }
+
+    #[test]
+    fn test_with_seed_is_reproducible() {
+        let level = || {
+            let mut app = App::new();
+            app.add_plugins(Random::new().with_seed(12345));
+            let mut streams = app.world.resource_mut::<RandomStreams>();
+            assert_eq!(streams.master_seed(), 12345);
+            draw(&mut streams, "level")
+        };
+        assert_eq!(level(), level());
+    }
+
+    #[derive(Resource, Default)]
+    struct Draws(Vec<u32>, Vec<u32>);
+
+    fn draw_a(mut rng: SystemRng, mut draws: ResMut<Draws>) {
+        draws.0.push(rng.range(0..1000));
+    }
+
+    fn draw_b(mut rng: SystemRng, mut draws: ResMut<Draws>) {
+        draws.1.push(rng.range(0..1000));
+    }
+
+    #[test]
+    fn test_system_rng_is_deterministic() {
+        let run = |systems_in_order: bool| {
+            let mut app = App::new();
+            app.add_plugins(Random::new().with_seed(12345));
+            app.init_resource::<Draws>();
+            if systems_in_order {
+                app.add_systems(Update, (draw_a, draw_b));
+            } else {
+                app.add_systems(Update, (draw_b, draw_a));
+            }
+            (0..100).for_each(|_| app.update());
+            app.world.remove_resource::<Draws>().unwrap()
+        };
+        let first = run(true);
+        let second = run(false);
+        assert_eq!(first.0, second.0);
+        assert_eq!(first.1, second.1);
+        assert_ne!(first.0, first.1);
+    }
+
+    #[test]
+    fn test_plugin_declares_streams() {
+        let mut app = App::new();
+        app.add_plugins(Random::new().with_stream("level"));
+        let streams = app.world.resource::<RandomStreams>();
+        assert!(streams.get("level").is_some());
+        assert!(streams.get("fx").is_none());
+        assert!(app.world.contains_resource::<RandomNumberGenerator>());
+    }
+}
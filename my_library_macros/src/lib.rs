@@ -0,0 +1,188 @@
+//! Procedural macros for `my_library`. Use them through the macros
+//! `my_library` exports, rather than depending on this crate directly.
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::quote;
+use serde::Deserialize;
+use syn::{
+    parse::{Parse, ParseStream},
+    Attribute, Ident, LitStr, Token, Visibility,
+};
+
+/// The parts of an asset manifest that name tags. Everything else in the
+/// manifest is ignored.
+#[derive(Deserialize)]
+struct Manifest {
+    assets: Vec<Tagged>,
+    #[serde(default)]
+    packs: Vec<Tagged>,
+}
+
+#[derive(Deserialize)]
+struct Tagged {
+    tag: String,
+}
+
+/// `$crate, #[attributes] visibility enum Name from "manifest.ron"`, as
+/// forwarded by `my_library::asset_tags!`.
+struct ManifestTags {
+    krate: TokenTree,
+    attributes: Vec<Attribute>,
+    visibility: Visibility,
+    name: Ident,
+    manifest: LitStr,
+}
+
+impl Parse for ManifestTags {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let krate = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let attributes = input.call(Attribute::parse_outer)?;
+        let visibility = input.parse()?;
+        input.parse::<Token![enum]>()?;
+        let name = input.parse()?;
+        let from: Ident = input.parse()?;
+        if from != "from" {
+            return Err(syn::Error::new(from.span(), "expected `from`"));
+        }
+        let manifest = input.parse()?;
+        Ok(Self {
+            krate,
+            attributes,
+            visibility,
+            name,
+            manifest,
+        })
+    }
+}
+
+/// Generates an `asset_tags!` enum with a variant for every tag in a
+/// manifest in the calling crate's `assets` directory. Called by
+/// `my_library::asset_tags!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn asset_tags_from_manifest(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as ManifestTags);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: ManifestTags) -> syn::Result<TokenStream> {
+    let error = |message: String| syn::Error::new(input.manifest.span(), message);
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| error("CARGO_MANIFEST_DIR isn't set; build with cargo".to_string()))?;
+    let path = std::path::Path::new(&manifest_dir)
+        .join("assets")
+        .join(input.manifest.value());
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| error(format!("unable to read {}: {e}", path.display())))?;
+    let tags = manifest_tags(&text).map_err(error)?;
+
+    let variants = tags
+        .iter()
+        .map(|tag| Ident::new(&variant_name(tag).unwrap(), Span::call_site()));
+    let ManifestTags {
+        krate,
+        attributes,
+        visibility,
+        name,
+        ..
+    } = input;
+    let path = path.to_string_lossy().into_owned();
+    Ok(quote! {
+        #krate::asset_tags! {
+            #(#attributes)*
+            #visibility enum #name {
+                #(#variants => #tags),*
+            }
+        }
+        // Rebuilds the enum when the manifest changes.
+        const _: &[u8] = include_bytes!(#path);
+    })
+}
+
+/// Every tag in the manifest, in order, checking that each makes a
+/// distinct variant name.
+fn manifest_tags(text: &str) -> Result<Vec<String>, String> {
+    let manifest: Manifest =
+        ron::from_str(text).map_err(|e| format!("unable to parse manifest: {e}"))?;
+    let tags: Vec<String> = manifest
+        .assets
+        .into_iter()
+        .chain(manifest.packs)
+        .map(|tagged| tagged.tag)
+        .collect();
+    let mut names = std::collections::HashMap::new();
+    for tag in tags.iter() {
+        let name = variant_name(tag)
+            .ok_or_else(|| format!("the tag {tag:?} can't be made into an enum variant"))?;
+        if let Some(other) = names.insert(name.clone(), tag) {
+            return Err(format!(
+                "the tags {other:?} and {tag:?} would both be called {name}"
+            ));
+        }
+    }
+    Ok(tags)
+}
+
+/// Converts a tag to UpperCamelCase, treating anything but letters and
+/// digits as a word break: `bg_far` becomes `BgFar`.
+fn variant_name(tag: &str) -> Option<String> {
+    let name: String = tag
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect();
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        .then_some(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(variant_name("wall").as_deref(), Some("Wall"));
+        assert_eq!(variant_name("bg_far").as_deref(), Some("BgFar"));
+        assert_eq!(variant_name("boss-theme.2").as_deref(), Some("BossTheme2"));
+        assert_eq!(variant_name("2d_wall"), None);
+        assert_eq!(variant_name("_"), None);
+    }
+
+    #[test]
+    fn test_manifest_tags() {
+        let manifest = r#"(
+            assets: [
+                (tag: "wall", filename: "wall.png", asset_type: Image),
+                (
+                    tag: "flappy",
+                    filename: "flappy.png",
+                    asset_type: SpriteSheet(tile_size: (62.0, 65.0), sprites_x: 4, sprites_y: 1),
+                ),
+            ],
+            packs: [(tag: "sprites", images: ["wall"])],
+        )"#;
+        assert_eq!(
+            manifest_tags(manifest).unwrap(),
+            ["wall", "flappy", "sprites"]
+        );
+
+        let clash = r#"(assets: [
+            (tag: "bg_far", filename: "a.png", asset_type: Image),
+            (tag: "bg-far", filename: "b.png", asset_type: Image),
+        ])"#;
+        assert_eq!(
+            manifest_tags(clash).unwrap_err(),
+            r#"the tags "bg_far" and "bg-far" would both be called BgFar"#
+        );
+        assert!(manifest_tags("(assets: [(tag: \"2d\")])").is_err());
+    }
+}